@@ -0,0 +1,67 @@
+//! 比较整体重建 [`ChartCache`] 与只更新单个画布的耗时.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rizlium_chart::prelude::*;
+
+const CANVASES: usize = 64;
+const KEYPOINTS: usize = 512;
+
+fn spline(len: usize, value: impl Fn(usize) -> f32) -> Spline<f32> {
+    (0..len)
+        .map(|i| KeyPoint {
+            time: i as f32,
+            value: value(i),
+            ease_type: EasingId::Start,
+            relevant: (),
+        })
+        .collect()
+}
+
+fn large_chart() -> Chart {
+    Chart {
+        themes: vec![],
+        theme_control: Spline::EMPTY,
+        lines: vec![],
+        canvases: (0..CANVASES)
+            .map(|c| Canvas {
+                x_pos: spline(1, |_| 0.),
+                speed: spline(KEYPOINTS, |i| 1. + ((i + c) % 4) as f32 * 0.25),
+            })
+            .collect(),
+        bpm: spline(KEYPOINTS, |i| 120. + (i % 8) as f32 * 10.),
+        cam_scale: spline(1, |_| 1.),
+        cam_move: spline(1, |_| 0.),
+    }
+}
+
+fn bench_cache(c: &mut Criterion) {
+    let mut chart = large_chart();
+    chart.canvases[CANVASES / 2].speed = spline(KEYPOINTS, |i| 3. - (i % 3) as f32);
+    let fresh = || ChartCache::from_chart(&large_chart());
+
+    let mut group = c.benchmark_group("chart_cache");
+    group.bench_function("update_from_chart", |b| {
+        b.iter_batched_ref(
+            fresh,
+            |cache| cache.update_from_chart(black_box(&chart)),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("update_canvas", |b| {
+        b.iter_batched_ref(
+            fresh,
+            |cache| cache.update_canvas(black_box(&chart), CANVASES / 2),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("update_beat_only", |b| {
+        b.iter_batched_ref(
+            fresh,
+            |cache| cache.update_beat_only(black_box(&chart)),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_cache);
+criterion_main!(benches);
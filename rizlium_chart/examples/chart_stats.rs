@@ -0,0 +1,114 @@
+//! 输出谱面的统计信息和难度估计.
+
+use std::{error, fs, path::PathBuf};
+
+use clap::{Parser, ValueEnum};
+use rizlium_chart::{
+    analysis::statistics::{statistics, ChartStatistics, DifficultyWeights, StatisticsConfig},
+    parse::rizline,
+    prelude::*,
+};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Rizlium,
+    Rizline,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "ChartStats", version, author)]
+struct Args {
+    /// 谱面 json 路径
+    #[arg()]
+    chart_path: PathBuf,
+    /// 谱面格式
+    #[arg(short, long, value_enum, default_value_t = Format::Rizlium)]
+    format: Format,
+    /// 难度权重 json, 未给出的项使用默认值
+    #[arg(short, long)]
+    weights: Option<PathBuf>,
+    /// 密度窗口长度 (秒)
+    #[arg(long, default_value_t = 2.)]
+    peak_window: f32,
+    /// 以 json 输出
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() -> Result<(), Box<dyn error::Error>> {
+    let args = Args::parse();
+    let file = fs::read(&args.chart_path)?;
+    let chart: Chart = match args.format {
+        Format::Rizlium => serde_json::from_slice(&file)?,
+        Format::Rizline => rizline::from_json_versioned(&file)?.try_into()?,
+    };
+    let weights: DifficultyWeights = match &args.weights {
+        Some(path) => serde_json::from_slice(&fs::read(path)?)?,
+        None => DifficultyWeights::default(),
+    };
+    let cache = ChartCache::from_chart(&chart);
+    let config = StatisticsConfig {
+        peak_window: args.peak_window,
+        ..Default::default()
+    };
+    let stats = statistics(&chart, &cache, &config);
+    let difficulty = weights.estimate(&stats);
+
+    if args.json {
+        let report = serde_json::json!({
+            "statistics": stats,
+            "difficulty": difficulty,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&stats, difficulty);
+    }
+    Ok(())
+}
+
+fn print_report(stats: &ChartStatistics, difficulty: f32) {
+    let counts = &stats.counts;
+    println!(
+        "notes:          {} (tap {}, drag {}, hold {})",
+        counts.total(),
+        counts.tap,
+        counts.drag,
+        counts.hold
+    );
+    println!("duration:       {:.2}s", stats.duration);
+    println!("notes/s:        {:.2}", stats.notes_per_second());
+    if let Some(peak) = stats.peak {
+        println!(
+            "peak:           {:.2}/s ({} notes, {:.2}s - {:.2}s)",
+            peak.per_second(),
+            peak.notes,
+            peak.start,
+            peak.end
+        );
+    }
+    println!("hold coverage:  {:.1}%", stats.hold_coverage * 100.);
+    println!(
+        "line speed:     avg {:.0}/s, max {:.0}/s",
+        stats.average_speed, stats.max_speed
+    );
+    if let Some((min, max)) = stats.bpm_range {
+        println!("bpm:            {min} - {max}");
+    }
+    println!("difficulty:     {difficulty:.2}");
+    println!();
+    println!("density ({}s buckets):", stats.bucket);
+    let max = stats.density.iter().copied().fold(0., f32::max);
+    for (i, density) in stats.density.iter().enumerate() {
+        let bar = if max > 0. {
+            (density / max * 40.).round() as usize
+        } else {
+            0
+        };
+        println!(
+            "{:>7.1}s {:>6.2} {}",
+            i as f32 * stats.bucket,
+            density,
+            "#".repeat(bar)
+        );
+    }
+}
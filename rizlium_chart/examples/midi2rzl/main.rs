@@ -3,10 +3,12 @@
 use std::{collections::HashMap, error, ffi::OsStr, fs, io, path::PathBuf};
 
 use clap::Parser;
-use midly::{Smf, Timing};
 use mp3lame_encoder::Id3Tag;
+use rizlium_chart::parse::{
+    midi::{self, MidiImportConfig},
+    ConvertError,
+};
 
-mod convert;
 mod midi_rendering;
 mod packaging;
 
@@ -30,6 +32,9 @@ struct Args {
     /// 输出 zip 路径
     #[arg(short, long = "output")]
     output_path: Option<PathBuf>,
+    /// 在每条线的开头放置音符
+    #[arg(long)]
+    notes: bool,
 }
 
 #[derive(Debug)]
@@ -53,7 +58,7 @@ fn detailed_errmsg(e: Box<dyn error::Error>) {
     if e.downcast_ref::<io::Error>().is_some() {
         eprintln!("..when tried to open the file")
     }
-    if e.downcast_ref::<midly::Error>().is_some() {
+    if e.downcast_ref::<ConvertError>().is_some() {
         eprintln!("..when tried to read the midi file.")
     }
 }
@@ -68,20 +73,12 @@ fn run(args: Args) -> Result<(), Box<dyn error::Error>> {
         sound_source,
         sample_rate,
         file_name,
+        config,
     } = process_args(args)?;
 
-    // 读取 MIDI 并生成谱面
+    // 读取 MIDI 并生成 Rizlium Chart
     let file = fs::read(&midi_path)?;
-    let smf = Smf::parse(&file)?;
-    let ticks_per_beat = match smf.header.timing {
-        Timing::Metrical(t) => t.as_int() as u32,
-        _ => Err(midly::Error::new(&midly::ErrorKind::Invalid(
-            "We support tick per beat times only.",
-        )))?,
-    };
-
-    // 生成 Rizlium Chart
-    let chart = convert::build_chart(&smf, ticks_per_beat);
+    let chart = midi::import(&file, &config)?;
     let chart_bytes = serde_json::to_vec(&chart)?;
 
     // 渲染或读取音乐
@@ -150,6 +147,7 @@ struct ProcessArgsResult {
     sound_source: PathBuf,
     sample_rate: u32,
     file_name: String,
+    config: MidiImportConfig,
 }
 
 enum SoundType {
@@ -164,6 +162,7 @@ fn process_args(args: Args) -> Result<ProcessArgsResult, Box<dyn error::Error>>
         bitrate: sample_rate,
         background_file,
         output_path,
+        notes,
     } = args;
     let mut output_path = output_path.unwrap_or_default();
     let file_name = midi_path
@@ -206,5 +205,12 @@ fn process_args(args: Args) -> Result<ProcessArgsResult, Box<dyn error::Error>>
         sound_type,
         sample_rate: sample_rate.unwrap_or(44100),
         file_name,
+        config: MidiImportConfig {
+            notes: midi::NoteMapping {
+                enabled: notes,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
     })
 }
@@ -0,0 +1,46 @@
+//! Rizlium Chart to Midi.
+
+use std::{error, fs, path::PathBuf};
+
+use clap::Parser;
+use rizlium_chart::{
+    parse::midi::{self, MidiExportConfig},
+    prelude::*,
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "Rzl2Midi", version, author)]
+struct Args {
+    /// 输入谱面 json 路径
+    #[arg()]
+    chart_path: PathBuf,
+    /// 输出 MIDI 路径, 默认与谱面同名
+    #[arg(short, long = "output")]
+    output_path: Option<PathBuf>,
+    /// 位于 x = 0 的音高
+    #[arg(long, default_value_t = 60)]
+    center_key: u8,
+    /// 画面宽度对应的音高数
+    #[arg(long, default_value_t = 88.)]
+    keys_across_view: f32,
+}
+
+fn main() -> Result<(), Box<dyn error::Error>> {
+    let args = Args::parse();
+    let chart: Chart = serde_json::from_slice(&fs::read(&args.chart_path)?)?;
+    let config = MidiExportConfig {
+        pitch: midi::PitchMapping {
+            center_key: args.center_key,
+            keys_across_view: args.keys_across_view,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let bytes = midi::export(&chart, &config)?;
+    let output_path = args
+        .output_path
+        .unwrap_or_else(|| args.chart_path.with_extension("mid"));
+    fs::write(&output_path, bytes)?;
+    println!("written to {}", output_path.display());
+    Ok(())
+}
@@ -0,0 +1,26 @@
+//! 对谱面的分析, 只读取谱面, 不做修改.
+pub mod playability;
+pub mod rhythm;
+pub mod statistics;
+
+use crate::prelude::*;
+
+/// 判定点在 `time` 时的横坐标.
+fn judge_x(chart: &ChartAndCache<'_, '_>, line: usize, time: f32) -> Option<f32> {
+    chart.line_pos_at(line, time, time).map(|[x, _]| x)
+}
+
+/// 判定点在 `time` 附近的横向速度 (每秒).
+fn judge_speed(chart: &ChartAndCache<'_, '_>, cache: &ChartCache, line: usize, time: f32) -> f32 {
+    const DELTA: f32 = 1e-2;
+    let (before, after) = (time - DELTA, time + DELTA);
+    let (Some(x1), Some(x2)) = (judge_x(chart, line, before), judge_x(chart, line, after)) else {
+        return 0.;
+    };
+    let real = cache.remap_beat(after) - cache.remap_beat(before);
+    if real > 0. {
+        ((x2 - x1) / real).abs()
+    } else {
+        0.
+    }
+}
@@ -0,0 +1,241 @@
+//! 游戏性检查: 找出结构正确但难以或无法游玩的部分.
+//!
+//! 镜头的移动和缩放暂不考虑, 与渲染一致.
+use super::{judge_speed, judge_x};
+use crate::{prelude::*, VIEW_RECT};
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+pub struct PlayabilityConfig {
+    /// 不同线上的音符间隔小于此值 (毫秒) 时难以分开击打.
+    pub min_gap_ms: f32,
+    /// 间隔小于此值 (毫秒) 的音符视为同时, 即双押.
+    pub chord_ms: f32,
+    /// 判定点横向速度 (每秒) 超过此值时不适合放置 drag.
+    pub max_drag_speed: f32,
+    /// 线段的判定点横向速度 (每秒) 超过此值时视为过快.
+    pub max_segment_speed: f32,
+}
+
+impl Default for PlayabilityConfig {
+    fn default() -> Self {
+        Self {
+            min_gap_ms: 40.,
+            chord_ms: 1.,
+            max_drag_speed: 1800.,
+            max_segment_speed: 4500.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub enum Location {
+    Note {
+        line: usize,
+        note: usize,
+    },
+    /// 从 `point` 到下一个点的线段.
+    Segment {
+        line: usize,
+        point: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub enum FindingKind {
+    /// 击打时音符位于 [`VIEW_RECT`] 之外.
+    OutOfView {
+        x: f32,
+    },
+    /// 音符不在所在线的时间范围内.
+    NoteOffLine,
+    /// 与另一条线上的音符间隔过短.
+    TooClose {
+        other_line: usize,
+        other_note: usize,
+        gap_ms: f32,
+    },
+    /// 与同一条线上的另一个长条重叠.
+    OverlappingHolds {
+        other_note: usize,
+    },
+    DragOnFastSegment {
+        speed: f32,
+    },
+    FastSegment {
+        speed: f32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub struct Finding {
+    pub location: Location,
+    /// 问题出现的时间 (beat).
+    pub time: f32,
+    pub severity: Severity,
+    pub kind: FindingKind,
+}
+
+/// 检查整个谱面, 结果按时间排列.
+pub fn check(chart: &Chart, cache: &ChartCache, config: &PlayabilityConfig) -> Vec<Finding> {
+    let with_cache = chart.with_cache(cache);
+    let mut findings = Vec::new();
+    for (line_idx, line) in chart.lines.iter().enumerate() {
+        for (note_idx, note) in line.notes.iter().enumerate() {
+            let location = Location::Note {
+                line: line_idx,
+                note: note_idx,
+            };
+            let mut push = |severity, kind| {
+                findings.push(Finding {
+                    location,
+                    time: note.time,
+                    severity,
+                    kind,
+                })
+            };
+            match judge_x(&with_cache, line_idx, note.time) {
+                None => push(Severity::Error, FindingKind::NoteOffLine),
+                Some(x) if !(VIEW_RECT[0][0]..=VIEW_RECT[1][0]).contains(&x) => {
+                    push(Severity::Error, FindingKind::OutOfView { x })
+                }
+                Some(_) => (),
+            }
+            if note.kind == NoteKind::Drag {
+                let speed = judge_speed(&with_cache, cache, line_idx, note.time);
+                if speed > config.max_drag_speed {
+                    push(Severity::Warning, FindingKind::DragOnFastSegment { speed });
+                }
+            }
+            if let NoteKind::Hold { end } = note.kind {
+                let overlapping = line.notes.iter().enumerate().find(|(other_idx, other)| {
+                    *other_idx != note_idx
+                        && matches!(other.kind, NoteKind::Hold { .. })
+                        && (note.time..end).contains(&other.time)
+                });
+                if let Some((other_note, _)) = overlapping {
+                    push(
+                        Severity::Error,
+                        FindingKind::OverlappingHolds { other_note },
+                    );
+                }
+            }
+        }
+
+        for (point_idx, pair) in line.points.points().windows(2).enumerate() {
+            let [start, end] = pair else { unreachable!() };
+            let real = cache.remap_beat(end.time) - cache.remap_beat(start.time);
+            let (Some([x1, _]), Some([x2, _])) = (
+                with_cache.pos_for_linepoint_at(line_idx, point_idx, start.time),
+                with_cache.pos_for_linepoint_at(line_idx, point_idx + 1, end.time),
+            ) else {
+                continue;
+            };
+            if real <= 0. {
+                continue;
+            }
+            let speed = ((x2 - x1) / real).abs();
+            if speed > config.max_segment_speed {
+                findings.push(Finding {
+                    location: Location::Segment {
+                        line: line_idx,
+                        point: point_idx,
+                    },
+                    time: start.time,
+                    severity: Severity::Warning,
+                    kind: FindingKind::FastSegment { speed },
+                });
+            }
+        }
+    }
+
+    let mut notes: Vec<_> = chart
+        .lines
+        .iter()
+        .enumerate()
+        .flat_map(|(line_idx, line)| {
+            line.notes
+                .iter()
+                .enumerate()
+                .map(move |(note_idx, note)| (line_idx, note_idx, note.time))
+        })
+        .map(|(line, note, time)| (line, note, time, cache.remap_beat(time) * 1000.))
+        .collect();
+    notes.sort_by(|a, b| a.3.total_cmp(&b.3));
+    for (i, &(line, note, time, real_ms)) in notes.iter().enumerate() {
+        let previous = notes[..i]
+            .iter()
+            .rev()
+            .take_while(|other| real_ms - other.3 < config.min_gap_ms)
+            .find(|other| other.0 != line && real_ms - other.3 > config.chord_ms);
+        if let Some(&(other_line, other_note, _, other_ms)) = previous {
+            findings.push(Finding {
+                location: Location::Note { line, note },
+                time,
+                severity: Severity::Warning,
+                kind: FindingKind::TooClose {
+                    other_line,
+                    other_note,
+                    gap_ms: real_ms - other_ms,
+                },
+            });
+        }
+    }
+
+    findings.sort_by(|a, b| a.time.total_cmp(&b.time));
+    findings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::{self, chart_with_notes};
+
+    #[test]
+    fn test_playability() {
+        use FindingKind::*;
+        let mut chart = chart_with_notes([
+            Note::new(0.5, NoteKind::Hold { end: 2. }),
+            Note::new(1., NoteKind::Hold { end: 1.5 }),
+            Note::new(4., NoteKind::Tap),
+        ]);
+        // 检查线段速度和视野需要移动的画布.
+        chart.canvases[0].speed = Spline::constant_speed(1.);
+        let mut line = fixture::line([(0., 0.), (4., 0.), (4.1, 400.), (8., 500.), (9., 500.)]);
+        line.notes = vec![
+            Note::new(4.05, NoteKind::Drag),
+            Note::new(8.5, NoteKind::Tap),
+            Note::new(10., NoteKind::Tap),
+        ];
+        chart.lines.push(line);
+        let cache = ChartCache::from_chart(&chart);
+        let findings = check(&chart, &cache, &PlayabilityConfig::default());
+        let kinds: Vec<_> = findings.iter().map(|finding| &finding.kind).collect();
+        assert!(matches!(
+            kinds.as_slice(),
+            [
+                OverlappingHolds { other_note: 1 },
+                FastSegment { .. },
+                DragOnFastSegment { .. },
+                TooClose {
+                    other_line: 0,
+                    other_note: 2,
+                    ..
+                },
+                OutOfView { .. },
+                NoteOffLine,
+            ]
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+//! 节奏对齐分析: 找出偏离拍线网格的音符.
+use std::collections::BTreeMap;
+
+use crate::prelude::*;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+
+/// 距 `time` 最近的 1/`division` 拍网格位置, 时间以 beat 为单位.
+///
+/// 网格以 `time` 所在 BPM 段的起点为准.
+pub fn nearest_grid(bpm: &Spline<f32>, time: f32, division: u32) -> f32 {
+    let anchor = grid_anchor(bpm, time);
+    let division = division as f32;
+    anchor + ((time - anchor) * division).round() / division
+}
+
+fn grid_anchor(bpm: &Spline<f32>, time: f32) -> f32 {
+    match bpm.keypoint_at(time) {
+        Ok(index) => bpm.points()[index].time,
+        Err(0) => bpm.start_time().unwrap_or(0.),
+        Err(_) => bpm.end_time().unwrap_or(0.),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RhythmConfig {
+    /// 依次尝试的细分, 应从粗到细排列.
+    pub divisions: Vec<u32>,
+    /// 三连音类的细分.
+    pub triplet_divisions: Vec<u32>,
+    /// 误差不超过此值 (毫秒) 视为对齐.
+    pub tolerance_ms: f32,
+    /// 既未对齐, 也不像摇摆或三连音, 且误差超过此值 (毫秒) 的音符视为离群.
+    pub outlier_ms: f32,
+    /// 后半拍落在这个范围内 (以拍为单位) 的八分音符视为摇摆.
+    pub swing_window: (f32, f32),
+    /// 直方图每格的宽度 (毫秒).
+    pub bin_ms: f32,
+}
+
+impl Default for RhythmConfig {
+    fn default() -> Self {
+        Self {
+            divisions: vec![1, 2, 4, 8, 16],
+            triplet_divisions: vec![3, 6, 12],
+            tolerance_ms: 5.,
+            outlier_ms: 10.,
+            swing_window: (0.55, 0.75),
+            bin_ms: 2.,
+        }
+    }
+}
+
+/// 不在直拍网格上但看起来是有意为之的节奏.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub enum Feel {
+    Swing,
+    Triplet,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub struct NoteAlignment {
+    pub line: usize,
+    pub note: usize,
+    pub time: f32,
+    /// 最接近的网格所属的细分.
+    pub division: u32,
+    pub grid_time: f32,
+    /// 音符相对网格的实际时间偏差 (毫秒), 偏晚为正.
+    pub error_ms: f32,
+    pub aligned: bool,
+    pub feel: Option<Feel>,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub struct RhythmReport {
+    /// 按线和音符顺序排列.
+    pub notes: Vec<NoteAlignment>,
+    /// 误差分布, 键为 `floor(error_ms / bin_ms)`.
+    pub histogram: BTreeMap<i32, usize>,
+    pub bin_ms: f32,
+    /// 离群音符在 [`Self::notes`] 中的下标.
+    pub outliers: Vec<usize>,
+}
+
+impl RhythmReport {
+    pub fn aligned_count(&self) -> usize {
+        self.notes.iter().filter(|note| note.aligned).count()
+    }
+}
+
+fn align(bpm: &Spline<f32>, cache: &ChartCache, time: f32, division: u32) -> (u32, f32, f32) {
+    let grid_time = nearest_grid(bpm, time, division);
+    let error_ms = (cache.remap_beat(time) - cache.remap_beat(grid_time)) * 1000.;
+    (division, grid_time, error_ms)
+}
+
+/// 分析每个音符与拍线网格的对齐情况.
+pub fn analyze(chart: &Chart, cache: &ChartCache, config: &RhythmConfig) -> RhythmReport {
+    let mut report = RhythmReport {
+        bin_ms: config.bin_ms,
+        ..Default::default()
+    };
+    let within = |(_, _, error_ms): &(u32, f32, f32)| error_ms.abs() <= config.tolerance_ms;
+    for (line_idx, line) in chart.lines.iter().enumerate() {
+        for (note_idx, note) in line.notes.iter().enumerate() {
+            let straight = config
+                .divisions
+                .iter()
+                .map(|division| align(&chart.bpm, cache, note.time, *division));
+            let nearest = straight
+                .clone()
+                .find(within)
+                .or_else(|| straight.min_by(|a, b| a.2.abs().total_cmp(&b.2.abs())));
+            let Some((mut division, mut grid_time, mut error_ms)) = nearest else {
+                continue;
+            };
+            let aligned = error_ms.abs() <= config.tolerance_ms;
+            let mut feel = None;
+            if !aligned {
+                let triplet = config
+                    .triplet_divisions
+                    .iter()
+                    .map(|division| align(&chart.bpm, cache, note.time, *division))
+                    .find(within);
+                if let Some(triplet) = triplet {
+                    (division, grid_time, error_ms) = triplet;
+                    feel = Some(Feel::Triplet);
+                } else {
+                    let offbeat = (note.time - grid_anchor(&chart.bpm, note.time)).rem_euclid(1.);
+                    let (start, end) = config.swing_window;
+                    if (start..=end).contains(&offbeat) {
+                        feel = Some(Feel::Swing);
+                    }
+                }
+            }
+            if !aligned && feel.is_none() && error_ms.abs() > config.outlier_ms {
+                report.outliers.push(report.notes.len());
+            }
+            *report
+                .histogram
+                .entry((error_ms / config.bin_ms).floor() as i32)
+                .or_default() += 1;
+            report.notes.push(NoteAlignment {
+                line: line_idx,
+                note: note_idx,
+                time: note.time,
+                division,
+                grid_time,
+                error_ms,
+                aligned: aligned || feel == Some(Feel::Triplet),
+                feel,
+            });
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::chart_with_notes;
+
+    #[test]
+    fn test_rhythm_report() {
+        let chart = chart_with_notes(
+            [1., 1.508, 2. + 1. / 3., 3.28, 4.6].map(|time| Note::new(time, NoteKind::Tap)),
+        );
+        let cache = ChartCache::from_chart(&chart);
+        let report = analyze(&chart, &cache, &RhythmConfig::default());
+        let notes = &report.notes;
+        assert!(notes[0].aligned && notes[0].division == 1);
+        assert!(notes[1].aligned && notes[1].division == 2);
+        assert!((notes[1].error_ms - 4.).abs() < 1e-2);
+        assert_eq!(notes[2].feel, Some(Feel::Triplet));
+        assert!(!notes[3].aligned && notes[3].feel.is_none());
+        assert_eq!(report.outliers, [3]);
+        assert_eq!(notes[4].feel, Some(Feel::Swing));
+        assert_eq!(report.histogram.values().sum::<usize>(), notes.len());
+    }
+}
@@ -0,0 +1,290 @@
+//! 谱面统计与难度估计.
+//!
+//! 除 BPM 外, 时间均为实际时间 (秒).
+use super::judge_speed;
+use crate::prelude::*;
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+pub struct StatisticsConfig {
+    /// 密度曲线每段的长度 (秒).
+    pub bucket: f32,
+    /// 寻找最密集段落时窗口的长度 (秒).
+    pub peak_window: f32,
+}
+
+impl Default for StatisticsConfig {
+    fn default() -> Self {
+        Self {
+            bucket: 1.,
+            peak_window: 2.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub struct NoteCounts {
+    pub tap: usize,
+    pub drag: usize,
+    pub hold: usize,
+}
+
+impl NoteCounts {
+    pub fn total(&self) -> usize {
+        self.tap + self.drag + self.hold
+    }
+    fn add(&mut self, kind: &NoteKind) {
+        match kind {
+            NoteKind::Tap => self.tap += 1,
+            NoteKind::Drag => self.drag += 1,
+            NoteKind::Hold { .. } => self.hold += 1,
+        }
+    }
+}
+
+/// 一段时间内的音符数.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub struct DensityWindow {
+    pub start: f32,
+    pub end: f32,
+    pub notes: usize,
+}
+
+impl DensityWindow {
+    pub fn per_second(&self) -> f32 {
+        let length = self.end - self.start;
+        if length > 0. {
+            self.notes as f32 / length
+        } else {
+            0.
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub struct ChartStatistics {
+    pub counts: NoteCounts,
+    /// 第一个音符到最后一个音符 (含长条结尾) 的时长.
+    pub duration: f32,
+    /// 从第一个音符开始, 每 `bucket` 秒的每秒音符数.
+    pub density: Vec<f32>,
+    pub bucket: f32,
+    /// 最密集的窗口, 没有音符时为 `None`.
+    pub peak: Option<DensityWindow>,
+    /// 至少有一个长条被按住的时间占 [`Self::duration`] 的比例.
+    pub hold_coverage: f32,
+    /// 击打时判定点的平均横向速度 (每秒).
+    pub average_speed: f32,
+    /// 击打时判定点的最大横向速度 (每秒).
+    pub max_speed: f32,
+    /// 最低与最高 BPM, 没有 BPM 时为 `None`.
+    pub bpm_range: Option<(f32, f32)>,
+}
+
+impl ChartStatistics {
+    /// 整个谱面的平均每秒音符数.
+    pub fn notes_per_second(&self) -> f32 {
+        if self.duration > 0. {
+            self.counts.total() as f32 / self.duration
+        } else {
+            0.
+        }
+    }
+}
+
+/// 统计整个谱面.
+pub fn statistics(chart: &Chart, cache: &ChartCache, config: &StatisticsConfig) -> ChartStatistics {
+    let with_cache = chart.with_cache(cache);
+    let mut stats = ChartStatistics {
+        bucket: config.bucket,
+        bpm_range: chart
+            .bpm
+            .points()
+            .iter()
+            .map(|p| p.value)
+            .fold(None, |range, bpm| {
+                Some(range.map_or((bpm, bpm), |(min, max): (f32, f32)| {
+                    (min.min(bpm), max.max(bpm))
+                }))
+            }),
+        ..Default::default()
+    };
+    let mut hits = Vec::new();
+    let mut holds = Vec::new();
+    let mut speed_sum = 0.;
+    for (line_idx, line) in chart.lines.iter().enumerate() {
+        for note in &line.notes {
+            stats.counts.add(&note.kind);
+            let hit = cache.remap_beat(note.time);
+            hits.push(hit);
+            if let NoteKind::Hold { end } = note.kind {
+                holds.push((hit, cache.remap_beat(end)));
+            }
+            let speed = judge_speed(&with_cache, cache, line_idx, note.time);
+            speed_sum += speed;
+            stats.max_speed = stats.max_speed.max(speed);
+        }
+    }
+    if hits.is_empty() {
+        return stats;
+    }
+    hits.sort_by(f32::total_cmp);
+    holds.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let first = hits[0];
+    let last = holds
+        .iter()
+        .map(|hold| hold.1)
+        .fold(hits[hits.len() - 1], f32::max);
+    stats.duration = last - first;
+    stats.average_speed = speed_sum / hits.len() as f32;
+
+    if config.bucket > 0. {
+        let buckets = (stats.duration / config.bucket).floor() as usize + 1;
+        let mut counts = vec![0usize; buckets];
+        for hit in &hits {
+            counts[((hit - first) / config.bucket) as usize] += 1;
+        }
+        stats.density = counts
+            .into_iter()
+            .map(|count| count as f32 / config.bucket)
+            .collect();
+    }
+
+    let mut end = 0;
+    for (start, &hit) in hits.iter().enumerate() {
+        while end < hits.len() && hits[end] < hit + config.peak_window {
+            end += 1;
+        }
+        if stats.peak.is_none_or(|peak| end - start > peak.notes) {
+            stats.peak = Some(DensityWindow {
+                start: hit,
+                end: hit + config.peak_window,
+                notes: end - start,
+            });
+        }
+    }
+
+    let mut covered = 0.;
+    let mut current: Option<(f32, f32)> = None;
+    for &(start, end) in &holds {
+        match &mut current {
+            Some((_, current_end)) if start <= *current_end => *current_end = current_end.max(end),
+            _ => {
+                if let Some((s, e)) = current.replace((start, end)) {
+                    covered += e - s;
+                }
+            }
+        }
+    }
+    if let Some((s, e)) = current {
+        covered += e - s;
+    }
+    if stats.duration > 0. {
+        stats.hold_coverage = covered / stats.duration;
+    }
+    stats
+}
+
+/// 难度估计中各项统计的权重.
+///
+/// 估计值是各项的线性组合, 只适合在同一组权重下相互比较.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize), serde(default))]
+pub struct DifficultyWeights {
+    /// 每秒音符数.
+    pub density: f32,
+    /// 最密集窗口的每秒音符数.
+    pub peak_density: f32,
+    /// 每 1000 单位/秒的平均判定点速度.
+    pub average_speed: f32,
+    /// 每 1000 单位/秒的最大判定点速度.
+    pub max_speed: f32,
+    pub hold_coverage: f32,
+    /// drag 占所有音符的比例, drag 较容易, 一般为负.
+    pub drag_ratio: f32,
+}
+
+impl Default for DifficultyWeights {
+    fn default() -> Self {
+        Self {
+            density: 0.8,
+            peak_density: 0.6,
+            average_speed: 1.5,
+            max_speed: 0.4,
+            hold_coverage: 1.,
+            drag_ratio: -2.,
+        }
+    }
+}
+
+impl DifficultyWeights {
+    /// 估计难度, 不小于 0.
+    pub fn estimate(&self, stats: &ChartStatistics) -> f32 {
+        let total = stats.counts.total();
+        if total == 0 {
+            return 0.;
+        }
+        let peak = stats.peak.map_or(0., |peak| peak.per_second());
+        let drag_ratio = stats.counts.drag as f32 / total as f32;
+        let estimate = self.density * stats.notes_per_second()
+            + self.peak_density * peak
+            + self.average_speed * stats.average_speed / 1000.
+            + self.max_speed * stats.max_speed / 1000.
+            + self.hold_coverage * stats.hold_coverage
+            + self.drag_ratio * drag_ratio;
+        estimate.max(0.)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::chart_with_notes;
+
+    #[test]
+    fn test_statistics() {
+        // 120 BPM, 一拍 0.5 秒.
+        let mut chart = chart_with_notes([
+            Note::new(0., NoteKind::Tap),
+            Note::new(1., NoteKind::Hold { end: 3. }),
+            Note::new(2., NoteKind::Hold { end: 4. }),
+            Note::new(8., NoteKind::Drag),
+        ]);
+        chart.bpm.push(KeyPoint {
+            time: 8.,
+            value: 180.,
+            ease_type: EasingId::Start,
+            relevant: (),
+        });
+        let cache = ChartCache::from_chart(&chart);
+        let stats = statistics(&chart, &cache, &StatisticsConfig::default());
+        assert_eq!(
+            stats.counts,
+            NoteCounts {
+                tap: 1,
+                drag: 1,
+                hold: 2
+            }
+        );
+        assert!((stats.duration - 4.).abs() < 1e-3);
+        assert_eq!(stats.density, [2., 1., 0., 0., 1.]);
+        let peak = stats.peak.unwrap();
+        assert_eq!(peak.notes, 3);
+        assert!((stats.hold_coverage - 0.375).abs() < 1e-3);
+        assert_eq!(stats.max_speed, 0.);
+        assert_eq!(stats.bpm_range, Some((120., 180.)));
+        let weights = DifficultyWeights::default();
+        let easier = weights.estimate(&stats);
+        chart.lines[0].notes.push(Note::new(8.5, NoteKind::Tap));
+        let cache = ChartCache::from_chart(&chart);
+        let harder = weights.estimate(&statistics(&chart, &cache, &StatisticsConfig::default()));
+        assert!(harder > easier);
+    }
+}
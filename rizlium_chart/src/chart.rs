@@ -1,16 +1,20 @@
 mod color;
 mod easing;
+mod hash;
 mod line;
 mod note;
 mod theme;
 mod time;
 
-use std::sync::OnceLock;
+use std::{collections::BTreeSet, sync::OnceLock};
 
 pub use color::*;
 pub use easing::*;
+pub use hash::*;
 pub use line::*;
 pub use note::*;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 #[cfg(feature = "deserialize")]
 use serde::Deserialize;
 #[cfg(feature = "serialize")]
@@ -24,6 +28,7 @@ use tracing::warn;
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Chart {
     pub themes: Vec<ThemeData>,
     pub theme_control: Spline<usize>,
@@ -71,6 +76,14 @@ impl Chart {
     pub fn note_count(&self) -> usize {
         self.lines.iter().map(|l| l.notes.len()).sum()
     }
+    /// 谱面中的音符用到的所有击打音效.
+    pub fn key_sounds(&self) -> BTreeSet<&str> {
+        self.lines
+            .iter()
+            .flat_map(|line| &line.notes)
+            .filter_map(|note| note.properties.sound.as_deref())
+            .collect()
+    }
     pub const fn with_cache<'a: 'b, 'b>(&'a self, cache: &'b ChartCache) -> ChartAndCache<'a, 'b> {
         ChartAndCache { chart: self, cache }
     }
@@ -82,6 +95,7 @@ impl Chart {
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Canvas {
     pub x_pos: Spline<f32>,
     pub speed: Spline<f32>,
@@ -112,53 +126,78 @@ impl ChartCache {
     }
     /// 用给定的 [`Chart`] 更新此 [`ChartCache`] .
     pub fn update_from_chart(&mut self, chart: &Chart) {
+        self.update_beat_only(chart);
+        self.update_canvases(chart);
+    }
+
+    /// 只更新 beat 映射.
+    ///
+    /// 画布高度由 `beat_remap` 计算而来, BPM 改变后通常还需要 [`Self::update_canvases`].
+    pub fn update_beat_only(&mut self, chart: &Chart) {
         self.update_beat(&chart.bpm);
         self.beat_remap = self.beat.clone_inverted();
-        self.canvas_y_by_real = chart
-            .canvases
-            .iter()
-            .map(|canvas| {
-                let mut points = canvas.speed.clone().points;
-                points.push(KeyPoint {
-                    time: points.last().unwrap().time + LARGE,
-                    value: 0.,
-                    ease_type: EasingId::Start,
-                    relevant: (),
-                });
-                points.iter_mut().fold(
-                    (0., 0., 0.0f32),
-                    |(last_start, last_real, last_value), keypoint| {
-                        if keypoint.ease_type != EasingId::Start {
-                            static ONCE: OnceLock<()> = OnceLock::new();
-                            if ONCE.set(()).is_ok() {
-                                warn!(
-                                    "non-constant speed {:?} is not supported (yet).",
-                                    keypoint.ease_type
-                                )
-                            }
+    }
 
-                            keypoint.ease_type = EasingId::Linear;
-                        }
-                        let this_real = self.beat_remap.value_padding(keypoint.time).unwrap();
-                        let pos = last_value.mul_add(this_real - last_real, last_start);
-                        let value = keypoint.value;
-                        keypoint.value = pos;
-                        keypoint.time = this_real;
-                        (pos, this_real, value)
-                    },
-                );
+    /// 重新计算所有 [`Canvas`] 的高度, 适用于画布被插入或删除的情况.
+    pub fn update_canvases(&mut self, chart: &Chart) {
+        self.canvas_y_by_real.clear();
+        self.real_to_canvas_y.clear();
+        for canvas in &chart.canvases {
+            let spline = self.canvas_y_spline(canvas);
+            self.real_to_canvas_y
+                .push(spline.is_invertible().then(|| spline.clone_inverted()));
+            self.canvas_y_by_real.push(spline);
+        }
+    }
 
-                points
-                    .into_iter()
-                    // .map(|k| k.with_relevant(()))
-                    .collect()
-            })
-            .collect();
-        self.real_to_canvas_y = self
-            .canvas_y_by_real
-            .iter()
-            .map(|s| s.is_invertible().then(|| s.clone_inverted()))
-            .collect();
+    /// 只重新计算第 `index` 个 [`Canvas`] 的高度.
+    ///
+    /// 画布数量与缓存不一致时改为调用 [`Self::update_canvases`].
+    pub fn update_canvas(&mut self, chart: &Chart, index: usize) {
+        if self.canvas_y_by_real.len() != chart.canvases.len() {
+            self.update_canvases(chart);
+            return;
+        }
+        let Some(canvas) = chart.canvases.get(index) else {
+            return;
+        };
+        let spline = self.canvas_y_spline(canvas);
+        self.real_to_canvas_y[index] = spline.is_invertible().then(|| spline.clone_inverted());
+        self.canvas_y_by_real[index] = spline;
+    }
+
+    fn canvas_y_spline(&self, canvas: &Canvas) -> Spline<f32> {
+        let mut points = canvas.speed.clone().points;
+        points.push(KeyPoint {
+            time: points.last().unwrap().time + LARGE,
+            value: 0.,
+            ease_type: EasingId::Start,
+            relevant: (),
+        });
+        points.iter_mut().fold(
+            (0., 0., 0.0f32),
+            |(last_start, last_real, last_value), keypoint| {
+                if keypoint.ease_type != EasingId::Start {
+                    static ONCE: OnceLock<()> = OnceLock::new();
+                    if ONCE.set(()).is_ok() {
+                        warn!(
+                            "non-constant speed {:?} is not supported (yet).",
+                            keypoint.ease_type
+                        )
+                    }
+
+                    keypoint.ease_type = EasingId::Linear;
+                }
+                let this_real = self.beat_remap.value_padding(keypoint.time).unwrap();
+                let pos = last_value.mul_add(this_real - last_real, last_start);
+                let value = keypoint.value;
+                keypoint.value = pos;
+                keypoint.time = this_real;
+                (pos, this_real, value)
+            },
+        );
+
+        points.into_iter().collect()
     }
 
     /// 一个正值, 表示canvas所处的高度.
@@ -189,9 +228,10 @@ impl ChartCache {
             let point = iter.next()?;
             let Some(last) = last_key else {
                 last_key = Some(point);
+                // 实际时间 0 对应第一个 BPM 点.
                 return Some(KeyPoint {
                     time: 0.,
-                    value: 0.0f32,
+                    value: point.time,
                     ease_type: EasingId::Linear,
                     relevant: (),
                 });
@@ -220,6 +260,9 @@ impl ChartCache {
     }
 }
 
+/// [`ChartAndCache::line_pos_at_clamped`] 与线两端保持的距离.
+pub const LINE_CLAMP_MARGIN: f32 = 0.01;
+
 pub struct ChartAndCache<'chart, 'cache> {
     chart: &'chart Chart,
     cache: &'cache ChartCache,
@@ -232,20 +275,80 @@ impl ChartAndCache<'_, '_> {
         point_idx: usize,
         game_time: f32,
     ) -> Option<[f32; 2]> {
-        let point = self
-            .chart
-            .lines
-            .get(line_idx)?
-            .points
-            .points()
-            .get(point_idx)?;
+        let line = self.chart.lines.get(line_idx)?;
+        let point = line.points.points().get(point_idx)?;
         Some([
             self.keypoint_releated_x(point, game_time)?,
-            self.cache.canvas_y_at(point.relevant.canvas, point.time)?
-                - self.cache.canvas_y_at(point.relevant.canvas, game_time)?,
+            self.line_distance(line_idx, point.relevant.canvas, game_time, point.time)?,
         ])
     }
 
+    /// 线上附着于 `canvas` 的点从 `from` 到 `to` 滚动的距离, 即线的速度倍率对画布滚动距离的积分.
+    ///
+    /// 与 [`Canvas::speed`] 相同, 倍率在相邻两个点之间保持不变.
+    pub fn line_distance(&self, line_idx: usize, canvas: usize, from: f32, to: f32) -> Option<f32> {
+        let points = self.chart.lines.get(line_idx)?.speed.points();
+        let canvas_y = |time| self.cache.canvas_y_at(canvas, time);
+        let (low, high) = if from <= to { (from, to) } else { (to, from) };
+        let mut index = points.partition_point(|point| point.time <= low);
+        let mut start = low;
+        let mut distance = 0.;
+        loop {
+            let speed = points
+                .get(index.saturating_sub(1))
+                .map_or(1., |point| point.value);
+            let end = points.get(index).map_or(high, |point| point.time.min(high));
+            distance += speed * (canvas_y(end)? - canvas_y(start)?);
+            if end >= high {
+                break;
+            }
+            start = end;
+            index += 1;
+        }
+        Some(if from <= to { distance } else { -distance })
+    }
+
+    /// [`Self::line_distance`] 的逆: 从 `from` 滚动 `distance` 后到达的时间.
+    pub fn line_distance_to_time(
+        &self,
+        line_idx: usize,
+        canvas: usize,
+        from: f32,
+        distance: f32,
+    ) -> Option<f32> {
+        let points = self.chart.lines.get(line_idx)?.speed.points();
+        let canvas_y = |time| self.cache.canvas_y_at(canvas, time);
+        let forward = distance >= 0.;
+        let mut index = points.partition_point(|point| point.time <= from);
+        let mut start = from;
+        let mut remaining = distance;
+        loop {
+            let speed = points
+                .get(index.saturating_sub(1))
+                .map_or(1., |point| point.value);
+            let end = if forward {
+                points.get(index)
+            } else {
+                index.checked_sub(1).map(|index| &points[index])
+            };
+            if let Some(end) = end {
+                let covered = speed * (canvas_y(end.time)? - canvas_y(start)?);
+                if (forward && covered < remaining) || (!forward && covered > remaining) {
+                    remaining -= covered;
+                    start = end.time;
+                    index = if forward { index + 1 } else { index - 1 };
+                    continue;
+                }
+            }
+            if speed == 0. {
+                return None;
+            }
+            return self
+                .cache
+                .canvas_y_to_time(canvas, canvas_y(start)? + remaining / speed);
+        }
+    }
+
     pub fn line_pos_at(&self, line_idx: usize, time: f32, game_time: f32) -> Option<[f32; 2]> {
         let line = self.chart.lines.get(line_idx)?;
         let index = line.points.keypoint_at(time).ok()?;
@@ -257,8 +360,7 @@ impl ChartAndCache<'_, '_> {
         let pos2 = self
             .pos_for_linepoint_at(line_idx, index + 1, game_time)
             .unwrap();
-        let point_y = self.cache.canvas_y_at(point1.relevant.canvas, time)?
-            - self.cache.canvas_y_at(point1.relevant.canvas, game_time)?;
+        let point_y = self.line_distance(line_idx, point1.relevant.canvas, game_time, time)?;
         Some([
             f32::ease(
                 pos1[0],
@@ -276,10 +378,13 @@ impl ChartAndCache<'_, '_> {
         game_time: f32,
     ) -> Option<[f32; 2]> {
         let line = self.chart.lines.get(line_idx)?;
-        time = time.clamp(
-            line.points.start_time()? + 0.01,
-            line.points.end_time()? - 0.01,
-        );
+        let (start, end) = (line.points.start_time()?, line.points.end_time()?);
+        time = if end - start < LINE_CLAMP_MARGIN * 2. {
+            // 线太短, 留不出两端的距离, 取中点.
+            (start + end) / 2.
+        } else {
+            time.clamp(start + LINE_CLAMP_MARGIN, end - LINE_CLAMP_MARGIN)
+        };
         self.line_pos_at(line_idx, time, game_time)
     }
 
@@ -305,3 +410,63 @@ impl ChartAndCache<'_, '_> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture;
+
+    fn assert_same_as_fresh(cache: &ChartCache, chart: &Chart) {
+        let fresh = ChartCache::from_chart(chart);
+        assert_eq!(cache.canvas_y_by_real.len(), fresh.canvas_y_by_real.len());
+        for index in 0..chart.canvases.len() {
+            for time in [0., 0.5, 1., 3., 7.5] {
+                assert_eq!(
+                    cache.canvas_y_at(index, time),
+                    fresh.canvas_y_at(index, time)
+                );
+                let y = fresh.canvas_y_at(index, time).unwrap();
+                assert_eq!(
+                    cache.canvas_y_to_time(index, y),
+                    fresh.canvas_y_to_time(index, y)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_canvas() {
+        let speed = |value| {
+            Spline::from(vec![KeyPoint {
+                time: 0.,
+                value,
+                ease_type: EasingId::Linear,
+                relevant: (),
+            }])
+        };
+        let mut chart = fixture::chart(vec![]);
+        chart.canvases[0].speed = speed(1.);
+        chart.canvases.push(chart.canvases[0].clone());
+        let mut cache = ChartCache::from_chart(&chart);
+        chart.canvases[1].speed.push(KeyPoint {
+            time: 2.,
+            value: 3.,
+            ease_type: EasingId::Linear,
+            relevant: (),
+        });
+        cache.update_canvas(&chart, 1);
+        assert_same_as_fresh(&cache, &chart);
+
+        // 插入或删除画布后, 缓存中的画布数量不一致, 改为更新所有画布.
+        let inserted = Canvas {
+            x_pos: chart.canvases[0].x_pos.clone(),
+            speed: speed(2.),
+        };
+        chart.canvases.insert(0, inserted);
+        cache.update_canvas(&chart, 0);
+        assert_same_as_fresh(&cache, &chart);
+        chart.canvases.remove(1);
+        cache.update_canvas(&chart, 1);
+        assert_same_as_fresh(&cache, &chart);
+    }
+}
@@ -3,6 +3,8 @@ use crate::tween;
 use super::Tween;
 use std::ops::{Add, Div};
 
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 #[cfg(feature = "deserialize")]
 use serde::Deserialize;
 #[cfg(feature = "serialize")]
@@ -12,6 +14,7 @@ use serde::Serialize;
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct ColorRGBA {
     pub r: f32,
     pub g: f32,
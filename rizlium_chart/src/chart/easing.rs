@@ -4,6 +4,8 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 use simple_easing::*;
 use tracing::{error, warn};
 
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 #[cfg(feature = "deserialize")]
 use serde::Deserialize;
 #[cfg(feature = "serialize")]
@@ -30,6 +32,11 @@ pub fn invlerp(y1: f32, y2: f32, y0: f32) -> f32 {
 #[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[cfg_attr(
+    feature = "schema",
+    schemars(bound = "T: JsonSchema, R: JsonSchema + Default + Serialize")
+)]
 pub struct KeyPoint<T: Tween, R = ()> {
     pub time: f32,
     pub value: T,
@@ -77,6 +84,8 @@ impl<R> KeyPoint<f32, R> {
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(bound = "KeyPoint<T, R>: JsonSchema"))]
 pub struct Spline<T: Tween, R = ()> {
     #[cfg_attr(
         any(feature = "serialize", feature = "deserialize"),
@@ -239,6 +248,37 @@ impl<T: Tween, R> Spline<T, R> {
     }
 }
 
+impl<T: Tween> Spline<T> {
+    /// 从 0 开始一直保持 `value` 的 [`Spline`], 使用 [`EasingId::Start`] 缓动.
+    ///
+    /// 不能用作画布的速度: 缓存按速度的缓动对位置插值, `Start` 缓动的速度
+    /// 只会让位置在下一个点处跳变, 画布不会移动. 画布速度应使用 [`Spline::constant_speed`].
+    pub fn constant(value: T) -> Self {
+        Self {
+            points: vec![KeyPoint {
+                time: 0.,
+                value,
+                ease_type: EasingId::Start,
+                relevant: (),
+            }],
+        }
+    }
+}
+
+impl Spline<f32> {
+    /// 保持 `speed` 不变的画布速度, 使用 [`EasingId::Linear`] 使画布匀速移动.
+    pub fn constant_speed(speed: f32) -> Self {
+        Self {
+            points: vec![KeyPoint {
+                time: 0.,
+                value: speed,
+                ease_type: EasingId::Linear,
+                relevant: (),
+            }],
+        }
+    }
+}
+
 impl<T: Tween, R> Default for Spline<T, R> {
     fn default() -> Self {
         Self { points: vec![] }
@@ -410,6 +450,7 @@ const EASING_MAP: [Easing; 16] = [
 #[derive(IntoPrimitive, TryFromPrimitive, Clone, Copy, Debug, Default, PartialEq, Eq, EnumIter)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[repr(u8)]
 pub enum EasingId {
     #[default]
@@ -0,0 +1,382 @@
+//! 谱面内容的稳定哈希, 可以在不同机器间比较.
+use std::{fmt, str::FromStr};
+
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+use snafu::Snafu;
+
+use super::*;
+
+/// 哈希算法的版本, 修改哈希的计算方式 (包括加入新的字段) 时必须增加.
+pub const CONTENT_HASH_VERSION: u32 = 2;
+
+/// 浮点数的精度, 差异小于此值的浮点数一般得到相同的哈希.
+///
+/// 精度属于哈希的计算方式, 修改时同样需要增加 [`CONTENT_HASH_VERSION`].
+pub const HASH_EPSILON: f32 = 1e-4;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 谱面内容的指纹, 以 `v{版本}:{哈希}` 的形式显示和解析.
+///
+/// 版本不同的指纹之间不能比较.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct ContentHash {
+    pub version: u32,
+    pub value: u64,
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}:{:016x}", self.version, self.value)
+    }
+}
+
+#[derive(Debug, Snafu, Clone, PartialEq, Eq)]
+#[snafu(display("invalid content hash `{input}`"))]
+pub struct ParseContentHashError {
+    input: String,
+}
+
+impl FromStr for ContentHash {
+    type Err = ParseContentHashError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = s.strip_prefix('v').and_then(|rest| {
+            let (version, value) = rest.split_once(':')?;
+            Some(Self {
+                version: version.parse().ok()?,
+                value: u64::from_str_radix(value, 16).ok()?,
+            })
+        });
+        parsed.ok_or_else(|| ParseContentHashError {
+            input: s.to_owned(),
+        })
+    }
+}
+
+/// 基于 FNV-1a 的哈希器.
+///
+/// 浮点数会先按 [`HASH_EPSILON`] 取整, 因此恰好落在取整边界两侧的两个值仍可能得到不同的哈希.
+#[derive(Debug, Clone)]
+pub struct ContentHasher {
+    state: u64,
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContentHasher {
+    pub fn new() -> Self {
+        let mut hasher = Self { state: FNV_OFFSET };
+        hasher.write_u64(CONTENT_HASH_VERSION.into());
+        hasher
+    }
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= u64::from(*byte);
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bytes(&[value.into()]);
+    }
+    /// 按精度取整, `-0.0` 与 `0.0` 相同, 所有 NaN 相同.
+    fn quantize(&self, value: f32) -> i64 {
+        if value.is_nan() {
+            i64::MIN
+        } else {
+            (f64::from(value) / f64::from(HASH_EPSILON)).round() as i64
+        }
+    }
+    /// 两个值取整后是否相同, 即写入后得到相同的哈希.
+    pub fn same_f32(&self, a: f32, b: f32) -> bool {
+        self.quantize(a) == self.quantize(b)
+    }
+    /// 写入按精度取整后的值.
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_bytes(&self.quantize(value).to_le_bytes());
+    }
+    pub fn finish(&self) -> ContentHash {
+        ContentHash {
+            version: CONTENT_HASH_VERSION,
+            value: self.state,
+        }
+    }
+}
+
+/// 可以计算稳定哈希的类型.
+///
+/// 字段按固定顺序写入, 与序列化时字段的顺序无关.
+pub trait StableHash {
+    fn stable_hash(&self, hasher: &mut ContentHasher);
+}
+
+impl StableHash for f32 {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        hasher.write_f32(*self);
+    }
+}
+
+impl StableHash for usize {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        hasher.write_usize(*self);
+    }
+}
+
+impl StableHash for bool {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        hasher.write_bool(*self);
+    }
+}
+
+impl StableHash for () {
+    fn stable_hash(&self, _hasher: &mut ContentHasher) {}
+}
+
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        hasher.write_usize(self.len());
+        self.iter().for_each(|item| item.stable_hash(hasher));
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        self.as_slice().stable_hash(hasher);
+    }
+}
+
+impl StableHash for EasingId {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        hasher.write_bytes(&[(*self).into()]);
+    }
+}
+
+impl StableHash for ColorRGBA {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        [self.r, self.g, self.b, self.a]
+            .iter()
+            .for_each(|c| c.stable_hash(hasher));
+    }
+}
+
+impl<T: Tween + StableHash, R: StableHash> StableHash for KeyPoint<T, R> {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        self.time.stable_hash(hasher);
+        self.value.stable_hash(hasher);
+        self.ease_type.stable_hash(hasher);
+        self.relevant.stable_hash(hasher);
+    }
+}
+
+impl<T: Tween + StableHash, R: StableHash> StableHash for Spline<T, R> {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        self.points.stable_hash(hasher);
+    }
+}
+
+impl StableHash for NoteKind {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        match self {
+            Self::Tap => hasher.write_bytes(&[0]),
+            Self::Hold { end } => {
+                hasher.write_bytes(&[1]);
+                end.stable_hash(hasher);
+            }
+            Self::Drag => hasher.write_bytes(&[2]),
+        }
+    }
+}
+
+impl StableHash for NoteProperties {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        match &self.color {
+            Some(color) => {
+                hasher.write_bool(true);
+                color.stable_hash(hasher);
+            }
+            None => hasher.write_bool(false),
+        }
+        self.scale.stable_hash(hasher);
+        hasher.write_bytes(&[self.variant as u8]);
+        match &self.sound {
+            Some(sound) => {
+                hasher.write_bool(true);
+                hasher.write_usize(sound.len());
+                hasher.write_bytes(sound.as_bytes());
+            }
+            None => hasher.write_bool(false),
+        }
+    }
+}
+
+impl StableHash for Note {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        self.time.stable_hash(hasher);
+        self.kind.stable_hash(hasher);
+        // 与默认值的比较同样按精度取整.
+        let properties = &self.properties;
+        let present = properties.color.is_some()
+            || !hasher.same_f32(properties.scale, NoteProperties::DEFAULT.scale)
+            || properties.variant != NoteVariant::Normal
+            || properties.sound.is_some();
+        hasher.write_bool(present);
+        if present {
+            properties.stable_hash(hasher);
+        }
+    }
+}
+
+impl StableHash for LineStyle {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        [self.width, self.dash, self.gap, self.glow]
+            .iter()
+            .for_each(|value| value.stable_hash(hasher));
+    }
+}
+
+impl StableHash for LinePointData {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        self.canvas.stable_hash(hasher);
+        self.color.stable_hash(hasher);
+        let style = self.style;
+        let default = LineStyle::DEFAULT;
+        let present = [
+            (style.width, default.width),
+            (style.dash, default.dash),
+            (style.gap, default.gap),
+            (style.glow, default.glow),
+        ]
+        .into_iter()
+        .any(|(value, default)| !hasher.same_f32(value, default));
+        hasher.write_bool(present);
+        if present {
+            style.stable_hash(hasher);
+        }
+    }
+}
+
+impl StableHash for Line {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        self.points.stable_hash(hasher);
+        self.notes.stable_hash(hasher);
+        self.ring_color.stable_hash(hasher);
+        self.line_color.stable_hash(hasher);
+        // 没有速度倍率时只写入长度 0.
+        self.speed.stable_hash(hasher);
+    }
+}
+
+impl StableHash for Canvas {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        self.x_pos.stable_hash(hasher);
+        self.speed.stable_hash(hasher);
+    }
+}
+
+impl StableHash for ThemeColor {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        self.background.stable_hash(hasher);
+        self.note.stable_hash(hasher);
+        self.fx.stable_hash(hasher);
+    }
+}
+
+impl StableHash for ThemeData {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        self.color.stable_hash(hasher);
+        self.is_challenge.stable_hash(hasher);
+    }
+}
+
+impl StableHash for Chart {
+    fn stable_hash(&self, hasher: &mut ContentHasher) {
+        self.themes.stable_hash(hasher);
+        self.theme_control.stable_hash(hasher);
+        self.lines.stable_hash(hasher);
+        self.canvases.stable_hash(hasher);
+        self.bpm.stable_hash(hasher);
+        self.cam_scale.stable_hash(hasher);
+        self.cam_move.stable_hash(hasher);
+    }
+}
+
+impl Chart {
+    /// 以 [`HASH_EPSILON`] 为精度的内容哈希.
+    pub fn content_hash(&self) -> ContentHash {
+        let mut hasher = ContentHasher::new();
+        self.stable_hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture;
+
+    fn chart() -> Chart {
+        let mut line = fixture::line([(0., 0.), (4., 100.)]);
+        line.notes = vec![
+            Note::new(1., NoteKind::Tap),
+            Note::new(2., NoteKind::Hold { end: 3. }),
+        ];
+        fixture::chart(vec![line])
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let original = chart();
+        let hash = original.content_hash();
+        // 改变哈希的计算方式时需要增加 `CONTENT_HASH_VERSION` 并更新这里.
+        assert_eq!(hash.to_string(), "v2:20fd4fff87162b7e");
+        assert_eq!(hash.to_string().parse(), Ok(hash));
+
+        let mut noisy = chart();
+        noisy.lines[0].notes[0].time += 1e-6;
+        noisy.bpm.points[0].value -= 1e-6;
+        assert_eq!(noisy.content_hash(), hash);
+
+        let mut changed = chart();
+        changed.lines[0].notes[1].kind = NoteKind::Hold { end: 3.5 };
+        assert_ne!(changed.content_hash(), hash);
+
+        let mut styled = chart();
+        styled.lines[0].points.points[0].relevant.style.width = 20.;
+        assert_ne!(styled.content_hash(), hash);
+        styled.lines[0].points.points[0].relevant.style.width = LineStyle::DEFAULT.width + 1e-6;
+        styled.lines[0].notes[0].properties.scale += 1e-6;
+        assert_eq!(styled.content_hash(), hash);
+
+        let mut sounded = chart();
+        sounded.lines[0].notes[0].properties.sound = Some("kick.wav".into());
+        assert_ne!(sounded.content_hash(), hash);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_content_hash_ignores_field_order() {
+        let json = serde_json::to_value(chart()).unwrap();
+        let serde_json::Value::Object(fields) = json else {
+            unreachable!()
+        };
+        let reversed: serde_json::Map<_, _> = fields.into_iter().rev().collect();
+        let text = serde_json::to_string(&reversed).unwrap();
+        let reparsed: Chart = serde_json::from_str(&text).unwrap();
+        assert_eq!(reparsed.content_hash(), chart().content_hash());
+    }
+}
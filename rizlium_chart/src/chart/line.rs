@@ -1,7 +1,10 @@
 use std::mem::replace;
 
 use super::*;
+use crate::tween;
 
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 #[cfg(feature = "deserialize")]
 use serde::Deserialize;
 #[cfg(feature = "serialize")]
@@ -11,20 +14,93 @@ use serde::Serialize;
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Line {
     pub points: Spline<f32, LinePointData>,
     pub notes: Vec<Note>,
     pub ring_color: Spline<ColorRGBA>,
     pub line_color: Spline<ColorRGBA>,
+    /// 速度倍率, 为空时倍率为 1.
+    ///
+    /// 线上各点到判定位置的距离为倍率对画布滚动距离的积分, 见 [`ChartAndCache::line_distance`].
+    #[cfg_attr(
+        any(feature = "serialize", feature = "deserialize"),
+        serde(default, skip_serializing_if = "Spline::is_empty")
+    )]
+    pub speed: Spline<f32>,
+}
+
+impl Line {
+    /// 第 `index` 个点与下一个点之间的线段在 `t` (`0.0..=1.0`) 处的样式.
+    ///
+    /// 样式按线性插值, 不受点的缓动影响.
+    pub fn style_at(&self, index: usize, t: f32) -> Option<LineStyle> {
+        let points = self.points.points();
+        let start = points.get(index)?.relevant.style;
+        let end = points
+            .get(index + 1)
+            .map_or(start, |point| point.relevant.style);
+        Some(LineStyle::lerp(start, end, t))
+    }
 }
 
 /// 线上的点的相关数据.
 #[derive(Debug, Clone, Default)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct LinePointData {
     pub canvas: usize,
     pub color: ColorRGBA,
+    #[cfg_attr(
+        any(feature = "serialize", feature = "deserialize"),
+        serde(default, skip_serializing_if = "LineStyle::is_default")
+    )]
+    pub style: LineStyle,
+}
+
+/// 线段的样式, 沿线段从起点的样式插值到终点的样式.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(default))]
+pub struct LineStyle {
+    /// 线宽.
+    pub width: f32,
+    /// 虚线中每段实线的长度, 为 0 时为实线.
+    pub dash: f32,
+    /// 虚线中相邻两段实线的间隔.
+    pub gap: f32,
+    /// 线两侧发光的宽度, 为 0 时不发光.
+    pub glow: f32,
+}
+
+impl LineStyle {
+    pub const DEFAULT: Self = Self {
+        width: 10.,
+        dash: 0.,
+        gap: 0.,
+        glow: 0.,
+    };
+    pub fn is_default(&self) -> bool {
+        *self == Self::DEFAULT
+    }
+    pub fn is_dashed(&self) -> bool {
+        self.dash > 0. && self.gap > 0.
+    }
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Tween for LineStyle {
+    fn lerp(x1: Self, x2: Self, t: f32) -> Self {
+        tween!((width, dash, gap, glow), x1, x2, t)
+    }
 }
 
 impl FromIterator<KeyPoint<f32, LinePointData>> for Line {
@@ -41,6 +117,46 @@ impl FromIterator<KeyPoint<f32, LinePointData>> for Line {
             notes: vec![],
             ring_color: Spline::EMPTY,
             line_color: Spline::EMPTY,
+            speed: Spline::EMPTY,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_line_style() {
+        let point = |time: f32, width: f32| KeyPoint {
+            time,
+            value: 0.,
+            ease_type: EasingId::QuadIn,
+            relevant: LinePointData {
+                style: LineStyle {
+                    width,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        };
+        let line = Line::from_iter([point(0., 10.), point(1., 30.)]);
+        assert_eq!(line.style_at(0, 0.5).unwrap().width, 20.);
+        assert_eq!(line.style_at(1, 0.5).unwrap().width, 30.);
+        assert!(line.style_at(2, 0.).is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_default_style_omitted() {
+        let data = LinePointData::default();
+        let json = serde_json::to_value(&data).unwrap();
+        assert!(json.get("style").is_none());
+        let styled: LinePointData = serde_json::from_str(
+            r#"{"canvas": 0, "color": {"r": 1, "g": 1, "b": 1, "a": 1}, "style": {"glow": 4}}"#,
+        )
+        .unwrap();
+        assert_eq!(styled.style.glow, 4.);
+        assert_eq!(styled.style.width, LineStyle::DEFAULT.width);
+    }
+}
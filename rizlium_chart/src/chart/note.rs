@@ -1,11 +1,17 @@
+use super::ColorRGBA;
+
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 #[cfg(feature = "deserialize")]
 use serde::Deserialize;
 #[cfg(feature = "serialize")]
 use serde::Serialize;
+use strum::EnumIter;
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum NoteKind {
     Tap,
     Hold { end: f32 },
@@ -26,13 +32,88 @@ impl PartialEq for NoteKind {
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Note {
     pub time: f32,
     pub kind: NoteKind,
+    #[cfg_attr(
+        any(feature = "serialize", feature = "deserialize"),
+        serde(default, skip_serializing_if = "NoteProperties::is_default")
+    )]
+    pub properties: NoteProperties,
 }
 
 impl Note {
     pub const fn new(time: f32, kind: NoteKind) -> Self {
-        Self { time, kind }
+        Self {
+            time,
+            kind,
+            properties: NoteProperties::DEFAULT,
+        }
+    }
+}
+
+/// 音符的外观变体.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum NoteVariant {
+    #[default]
+    Normal,
+    /// 高亮的边框, 用于强调.
+    Highlight,
+    /// 半透明.
+    Faint,
+}
+
+/// 音符的可选属性, 全部为默认值时不写入谱面.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(default))]
+pub struct NoteProperties {
+    /// 代替主题中音符颜色的颜色.
+    pub color: Option<ColorRGBA>,
+    pub scale: f32,
+    pub variant: NoteVariant,
+    /// 击打音效在谱面包中的路径.
+    pub sound: Option<String>,
+}
+
+impl NoteProperties {
+    pub const DEFAULT: Self = Self {
+        color: None,
+        scale: 1.,
+        variant: NoteVariant::Normal,
+        sound: None,
+    };
+    pub fn is_default(&self) -> bool {
+        *self == Self::DEFAULT
+    }
+}
+
+impl Default for NoteProperties {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_note_properties() {
+        let json = serde_json::to_value(Note::new(1., NoteKind::Tap)).unwrap();
+        assert!(json.get("properties").is_none());
+        let note: Note = serde_json::from_str(
+            r#"{"time": 1, "kind": "Tap", "properties": {"scale": 1.5, "sound": "kick.wav"}}"#,
+        )
+        .unwrap();
+        assert_eq!(note.properties.scale, 1.5);
+        assert_eq!(note.properties.variant, NoteVariant::Normal);
+        assert_eq!(note.properties.sound.as_deref(), Some("kick.wav"));
     }
 }
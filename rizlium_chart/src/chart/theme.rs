@@ -1,6 +1,8 @@
 use crate::tween;
 
 use super::{ColorRGBA, Tween};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 #[cfg(feature = "deserialize")]
 use serde::Deserialize;
 #[cfg(feature = "serialize")]
@@ -18,6 +20,7 @@ macro_rules! fields_str {
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct ThemeData {
     pub color: ThemeColor,
     pub is_challenge: bool,
@@ -26,6 +29,7 @@ pub struct ThemeData {
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct ThemeColor {
     pub background: ColorRGBA,
     pub note: ColorRGBA,
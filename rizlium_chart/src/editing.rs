@@ -1,13 +1,24 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    time::{Duration, Instant},
+};
 
 use crate::prelude::Chart;
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
 use snafu::Snafu;
+use tracing::error;
 
 use self::chart_path::LinePath;
+use self::commands::Nop;
 pub use self::{
+    change::{ChangeKind, ChangeTarget, ChartChange},
     chart_path::NotePath,
     commands::{ChartCommand, ChartCommands},
 };
+pub mod change;
 /// Representation of a chart item
 pub mod chart_path;
 pub mod commands;
@@ -32,18 +43,120 @@ pub enum ChartConflictError {
     NoSuchCanvas {
         canvas: usize,
     },
+    InvalidTransform {
+        reason: &'static str,
+    },
+    InvalidSplitTime {
+        line_path: LinePath,
+        time: f32,
+    },
+    InvalidJoin {
+        first: LinePath,
+        second: LinePath,
+    },
 }
 
 type Result<T> = std::result::Result<T, ChartConflictError>;
 
-#[derive(Default)]
+/// 默认的合并时间窗口, 见 [`EditHistory::set_coalesce_window`].
+pub const DEFAULT_COALESCE_WINDOW: Duration = Duration::from_millis(500);
+
+/// 编辑历史, 以树的形式保存所有分支.
+///
+/// 撤销后再进行新的编辑会在当前节点下创建新的分支, 原有的分支不会丢失.
+/// 序列化时只保存历史本身, 合并状态与命令日志不会被保存.
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct EditHistory {
-    history_descriptions: Vec<Cow<'static, str>>,
-    inverse_history: Vec<ChartCommands>,
+    /// 第一个节点是根节点, 表示尚未进行任何编辑.
+    nodes: Vec<HistoryNode>,
+    current: usize,
     preedit_data: Vec<PreeditData>,
-    redo_cache: Vec<ChartCommands>,
+    /// 最后一条历史可被合并时的状态, 为 `None` 时不进行合并.
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(skip))]
+    last_push: Option<LastPush>,
+    #[cfg_attr(
+        any(feature = "serialize", feature = "deserialize"),
+        serde(skip, default = "default_coalesce_window")
+    )]
+    coalesce_window: Duration,
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(skip))]
+    current_gesture: Option<u64>,
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(skip))]
+    next_gesture: u64,
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(skip))]
+    log: ApplyLog,
+}
+
+/// 历史树上的一个节点, 表示从父节点到此节点的一次编辑.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct HistoryNode {
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// 重做时进入的子节点, 即最近一次经过的分支.
+    active_child: Option<usize>,
+    /// 节点已应用时为逆命令, 否则为正向命令.
+    command: ChartCommands,
+    description: Cow<'static, str>,
 }
 
+impl HistoryNode {
+    fn new(parent: Option<usize>, command: ChartCommands, description: Cow<'static, str>) -> Self {
+        Self {
+            parent,
+            children: Vec::new(),
+            active_child: None,
+            command,
+            description,
+        }
+    }
+
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+
+    pub fn active_child(&self) -> Option<usize> {
+        self.active_child
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[cfg(feature = "deserialize")]
+fn default_coalesce_window() -> Duration {
+    DEFAULT_COALESCE_WINDOW
+}
+
+struct LastPush {
+    at: Instant,
+    gesture: Option<u64>,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self {
+            nodes: vec![HistoryNode::new(None, Nop.into(), "Initial".into())],
+            current: 0,
+            preedit_data: Vec::new(),
+            last_push: None,
+            coalesce_window: DEFAULT_COALESCE_WINDOW,
+            current_gesture: None,
+            next_gesture: 0,
+            log: ApplyLog::default(),
+        }
+    }
+}
+
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct PreeditData {
     inverse: ChartCommands,
     description: Cow<'static, str>,
@@ -60,21 +173,109 @@ impl PreeditData {
 }
 
 impl EditHistory {
+    /// 应用并记录一条编辑, 作为当前节点的新子节点.
+    ///
+    /// 若它与上一条历史作用于同一对象, 且处于同一手势内或在合并时间窗口内,
+    /// 则按照命令自身的 [`ChartCommand::merge`] 规则合并为一条历史.
+    /// 已有子节点的节点不会被合并, 以免破坏其他分支.
     pub fn push(&mut self, edit: impl Into<ChartCommands>, chart: &mut Chart) -> Result<()> {
         let command = edit.into();
-        self.push_direct(command, chart)?;
-        self.redo_cache.clear();
-        Ok(())
-    }
-
-    fn push_direct(&mut self, command: ChartCommands, chart: &mut Chart) -> Result<()> {
         command.validate(chart)?;
         let desc = command.description();
-        let inversed = command.apply(chart)?;
-        self.inverse_history.push(inversed);
-        self.history_descriptions.push(desc);
+        let inversed = self.log.apply(command, chart)?;
+        let now = Instant::now();
+        let merged = self.can_coalesce(now) && {
+            let current = &mut self.nodes[self.current];
+            current.parent.is_some()
+                && current.children.is_empty()
+                && current.command.merge(&inversed)
+        };
+        if !merged {
+            self.add_node(inversed, desc);
+        }
+        self.last_push = Some(LastPush {
+            at: now,
+            gesture: self.current_gesture,
+        });
         Ok(())
     }
+
+    /// 在当前节点下添加一个已应用的节点并移动到它.
+    fn add_node(&mut self, inversed: ChartCommands, description: Cow<'static, str>) {
+        let index = self.nodes.len();
+        self.nodes
+            .push(HistoryNode::new(Some(self.current), inversed, description));
+        let current = &mut self.nodes[self.current];
+        current.children.push(index);
+        current.active_child = Some(index);
+        self.current = index;
+        self.last_push = None;
+    }
+
+    fn can_coalesce(&self, now: Instant) -> bool {
+        let Some(last) = &self.last_push else {
+            return false;
+        };
+        (last.gesture.is_some() && last.gesture == self.current_gesture)
+            || now.saturating_duration_since(last.at) <= self.coalesce_window
+    }
+
+    /// 设置合并时间窗口. 设为 [`Duration::ZERO`] 时只在手势内合并.
+    pub fn set_coalesce_window(&mut self, window: Duration) {
+        self.coalesce_window = window;
+    }
+
+    pub fn coalesce_window(&self) -> Duration {
+        self.coalesce_window
+    }
+
+    /// 开始一次手势 (如一次拖动). 在 [`Self::end_gesture`] 之前, 对同一对象的连续编辑总会合并.
+    pub fn begin_gesture(&mut self) {
+        self.current_gesture = Some(self.next_gesture);
+        self.next_gesture += 1;
+    }
+
+    pub fn end_gesture(&mut self) {
+        self.current_gesture = None;
+    }
+
+    /// 开启或关闭命令日志.
+    ///
+    /// 开启后, 每条实际应用到谱面上的命令 (包括预编辑, 撤销与重做) 都会按顺序记录,
+    /// 用 [`replay`] 将它们依次应用到编辑开始时的谱面上即可得到相同的结果.
+    pub fn set_journaling(&mut self, enabled: bool) {
+        if enabled {
+            self.log.journal.get_or_insert_with(Vec::new);
+        } else {
+            self.log.journal = None;
+        }
+    }
+
+    pub fn is_journaling(&self) -> bool {
+        self.log.journal.is_some()
+    }
+
+    /// 取出上次调用以来应用到谱面上的改动, 按发生顺序排列.
+    ///
+    /// 改动总是会被记录, 使用者应定期取出.
+    pub fn take_changes(&mut self) -> Vec<ChartChange> {
+        std::mem::take(&mut self.log.changes)
+    }
+
+    /// 取出上次调用以来记录的命令.
+    pub fn take_journal(&mut self) -> Vec<ChartCommands> {
+        self.log
+            .journal
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// 阻止下一条编辑并入当前最后一条历史.
+    pub fn break_coalescing(&mut self) {
+        self.last_push = None;
+    }
+
     pub fn replace_last_preedit(
         &mut self,
         edit: impl Into<ChartCommands>,
@@ -84,7 +285,7 @@ impl EditHistory {
         command.validate(chart)?;
         self.discard_last_preedit(chart)?;
         let desc = command.description();
-        let command_inversed = command.apply(chart)?;
+        let command_inversed = self.log.apply(command, chart)?;
         self.preedit_data.push(PreeditData {
             inverse: command_inversed,
             description: desc,
@@ -94,7 +295,7 @@ impl EditHistory {
 
     pub fn discard_last_preedit(&mut self, chart: &mut Chart) -> Result<()> {
         if let Some(last) = self.preedit_data.pop() {
-            last.inverse.apply(chart)?;
+            self.log.apply(last.inverse, chart)?;
         };
         Ok(())
     }
@@ -106,82 +307,524 @@ impl EditHistory {
         let command: ChartCommands = edit.into();
         command.validate(chart)?;
         let desc = command.description();
-        let command_inversed = command.apply(chart)?;
+        let command_inversed = self.log.apply(command, chart)?;
         self.preedit_data.push(PreeditData {
             inverse: command_inversed,
             description: desc,
         });
         Ok(())
     }
+    /// 撤销所有预编辑. 即使其中某条失败, 其余的也会继续撤销, 返回遇到的第一个错误.
     pub fn discard_preedit(&mut self, chart: &mut Chart) -> Result<()> {
-        self.preedit_data.drain(..).rev().try_for_each(|data| {
-            dbg!(data.inverse).apply(chart)?;
-            Ok(())
-        })?;
-        Ok(())
+        let mut result = Ok(());
+        for data in std::mem::take(&mut self.preedit_data).into_iter().rev() {
+            if let Err(err) = self.log.apply(data.inverse, chart) {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+
+    /// 在一个事务中执行 `f`, 其中应用的所有命令作为一条历史提交.
+    ///
+    /// `f` 返回错误时, 已经应用的命令会按相反顺序撤销, 谱面恢复原样, 历史也不会改变.
+    /// 开始前会先撤销所有预编辑.
+    pub fn transaction<T>(
+        &mut self,
+        chart: &mut Chart,
+        f: impl FnOnce(&mut Transaction<'_>) -> Result<T>,
+    ) -> Result<T> {
+        self.discard_preedit(chart)?;
+        let mut transaction = Transaction {
+            chart,
+            log: &mut self.log,
+            inverses: Vec::new(),
+            descriptions: Vec::new(),
+        };
+        match f(&mut transaction) {
+            Ok(value) => {
+                let Transaction {
+                    inverses,
+                    descriptions,
+                    ..
+                } = transaction;
+                if !inverses.is_empty() {
+                    let desc = squash_description(descriptions);
+                    self.add_node(
+                        commands::CommandSequence { commands: inverses }.into(),
+                        desc,
+                    );
+                }
+                Ok(value)
+            }
+            Err(err) => {
+                transaction.rollback();
+                Err(err)
+            }
+        }
     }
     pub fn submit_preedit(&mut self) {
-        let (mut v1, mut v2): (Vec<_>, Vec<_>) = self
-            .preedit_data
-            .drain(..)
-            .map(|data| (data.inverse, data.description))
-            .unzip();
-        self.inverse_history.append(&mut v1);
-        self.history_descriptions.append(&mut v2);
-        self.redo_cache.clear();
+        for data in std::mem::take(&mut self.preedit_data) {
+            self.add_node(data.inverse, data.description);
+        }
     }
     /// Like `submit_preedit`, but also squash preedits into a single command.
+    ///
+    /// 描述取自被合并的预编辑, 不会包含 [`commands::Nop`].
     pub fn submit_preedit_squash(&mut self) {
-        let v1: Vec<_> = self
+        let (v1, descriptions): (Vec<_>, Vec<_>) = self
             .preedit_data
             .drain(..)
-            .map(|data| data.inverse)
-            .collect();
+            .filter(|data| !matches!(data.inverse, ChartCommands::Nop(_)))
+            .map(|data| (data.inverse, data.description))
+            .unzip();
+        if v1.is_empty() {
+            return;
+        }
+        let desc = squash_description(descriptions);
         let squashed_command = commands::CommandSequence { commands: v1 };
-        let desc = squashed_command.description();
-        self.inverse_history.push(squashed_command.into());
-        self.history_descriptions.push(desc);
-        self.redo_cache.clear();
+        self.add_node(squashed_command.into(), desc);
     }
-    pub fn history_descriptions(&self) -> &[Cow<'static, str>] {
-        self.history_descriptions.as_slice()
+    /// 从根节点到当前节点路径上的编辑描述.
+    pub fn history_descriptions(&self) -> Vec<&str> {
+        let mut path = self.path_to(self.current);
+        path.reverse();
+        path.into_iter()
+            .map(|index| self.nodes[index].description())
+            .collect()
     }
 
     pub fn preedit_datas(&self) -> &[PreeditData] {
         self.preedit_data.as_slice()
     }
 
-    pub fn gen_redo_descriptions(&self) -> impl ExactSizeIterator<Item = Cow<'static, str>> + '_ {
-        self.redo_cache.iter().map(|c| c.description())
+    /// 沿着 [`HistoryNode::active_child`] 依次重做时会经过的编辑描述.
+    pub fn gen_redo_descriptions(&self) -> impl Iterator<Item = &str> + '_ {
+        std::iter::successors(self.nodes[self.current].active_child, |index| {
+            self.nodes[*index].active_child
+        })
+        .map(|index| self.nodes[index].description())
+    }
+
+    /// 历史树的所有节点, 下标即节点编号. 编号为 [`Self::root`] 的节点不对应任何编辑.
+    pub fn nodes(&self) -> &[HistoryNode] {
+        &self.nodes
+    }
+
+    pub const fn root(&self) -> usize {
+        0
+    }
+
+    /// 当前所处的节点, 即谱面当前状态对应的节点.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// 从 `node` 到根节点 (不含) 的节点编号.
+    fn path_to(&self, node: usize) -> Vec<usize> {
+        std::iter::successors(Some(node), |index| self.nodes[*index].parent)
+            .filter(|index| *index != self.root())
+            .collect()
     }
 
     pub fn undo(&mut self, chart: &mut Chart) -> Result<()> {
         self.discard_preedit(chart)?;
-        let Some(history) = self.inverse_history.pop() else {
+        self.last_push = None;
+        self.step_up(chart)
+    }
+
+    /// 沿着 [`HistoryNode::active_child`] 重做, 没有记录时进入最新的分支.
+    pub fn redo(&mut self, chart: &mut Chart) -> Result<()> {
+        self.discard_preedit(chart)?;
+        self.last_push = None;
+        let current = &self.nodes[self.current];
+        let Some(child) = current.active_child.or(current.children.last().copied()) else {
+            return Ok(());
+        };
+        self.step_down(child, chart)
+    }
+
+    fn step_up(&mut self, chart: &mut Chart) -> Result<()> {
+        let index = self.current;
+        let Some(parent) = self.nodes[index].parent else {
             return Ok(());
         };
-        self.history_descriptions.pop();
-        let inversed = history.apply(chart)?;
-        self.redo_cache.push(inversed);
+        self.apply_node(index, chart)?;
+        self.nodes[parent].active_child = Some(index);
+        self.current = parent;
         Ok(())
     }
-    pub fn redo(&mut self, chart: &mut Chart) -> Result<()> {
+
+    /// `child` 必须是当前节点的子节点.
+    fn step_down(&mut self, child: usize, chart: &mut Chart) -> Result<()> {
+        debug_assert_eq!(self.nodes[child].parent, Some(self.current));
+        self.apply_node(child, chart)?;
+        self.nodes[self.current].active_child = Some(child);
+        self.current = child;
+        Ok(())
+    }
+
+    /// 应用节点上的命令, 并把它替换为逆命令. 失败时节点保留原来的命令.
+    fn apply_node(&mut self, index: usize, chart: &mut Chart) -> Result<()> {
+        let node = &mut self.nodes[index];
+        node.command.validate(chart)?;
+        node.command = self.log.apply(node.command.clone(), chart)?;
+        Ok(())
+    }
+
+    /// 跳转到任意节点: 先撤销到两者的公共祖先, 再沿路径重做到目标节点.
+    ///
+    /// 中途失败时停留在已经到达的节点上.
+    pub fn jump_to(&mut self, target: usize, chart: &mut Chart) -> Result<()> {
+        if target >= self.nodes.len() {
+            return Ok(());
+        }
         self.discard_preedit(chart)?;
-        let Some(redo) = self.redo_cache.pop() else {
+        self.last_push = None;
+        let mut down = self.path_to(target);
+        down.push(self.root());
+        let up = self.path_to(self.current);
+        let common = up
+            .iter()
+            .copied()
+            .chain(Some(self.root()))
+            .find(|index| down.contains(index))
+            .unwrap_or(self.root());
+        while self.current != common {
+            self.step_up(chart)?;
+        }
+        let position = down.iter().position(|index| *index == common).unwrap();
+        for child in down[..position].iter().rev() {
+            self.step_down(*child, chart)?;
+        }
+        Ok(())
+    }
+
+    /// 跳转到当前节点的相邻兄弟分支, `offset` 为正时向较新的分支移动.
+    pub fn switch_branch(&mut self, offset: isize, chart: &mut Chart) -> Result<()> {
+        let Some(parent) = self.nodes[self.current].parent else {
             return Ok(());
         };
-        self.push_direct(redo, chart)
+        let siblings = &self.nodes[parent].children;
+        let position = siblings
+            .iter()
+            .position(|index| *index == self.current)
+            .unwrap();
+        let target = position
+            .checked_add_signed(offset)
+            .and_then(|position| siblings.get(position).copied());
+        match target {
+            Some(target) => self.jump_to(target, chart),
+            None => Ok(()),
+        }
     }
 
     pub fn can_undo(&self) -> bool {
-        !self.inverse_history.is_empty()
+        self.current != self.root()
     }
 
     pub fn can_redo(&self) -> bool {
-        !self.redo_cache.is_empty()
+        !self.nodes[self.current].children.is_empty()
     }
 
     pub fn has_preedit(&self) -> bool {
         !self.preedit_data.is_empty()
     }
 }
+
+/// 见 [`EditHistory::transaction`].
+pub struct Transaction<'a> {
+    chart: &'a mut Chart,
+    log: &'a mut ApplyLog,
+    inverses: Vec<ChartCommands>,
+    descriptions: Vec<Cow<'static, str>>,
+}
+
+impl Transaction<'_> {
+    /// 验证并应用一条命令.
+    pub fn apply(&mut self, edit: impl Into<ChartCommands>) -> Result<()> {
+        let command = edit.into();
+        command.validate(self.chart)?;
+        let desc = command.description();
+        let inversed = self.log.apply(command, self.chart)?;
+        self.inverses.push(inversed);
+        self.descriptions.push(desc);
+        Ok(())
+    }
+
+    /// 事务中当前的谱面.
+    pub fn chart(&self) -> &Chart {
+        self.chart
+    }
+
+    fn rollback(self) {
+        for inverse in self.inverses.into_iter().rev() {
+            if let Err(err) = self.log.apply(inverse, self.chart) {
+                error!("failed to roll back command: {err:?}");
+            }
+        }
+    }
+}
+
+/// 记录通过 [`EditHistory`] 应用的命令及其造成的改动.
+#[derive(Default)]
+struct ApplyLog {
+    /// 开启时记录所有实际应用到谱面上的命令.
+    journal: Option<Vec<ChartCommands>>,
+    changes: Vec<ChartChange>,
+}
+
+impl ApplyLog {
+    fn apply(&mut self, command: ChartCommands, chart: &mut Chart) -> Result<ChartCommands> {
+        let logged = self.journal.is_some().then(|| command.clone());
+        let inversed = command.apply(chart)?;
+        if let (Some(journal), Some(logged)) = (&mut self.journal, logged) {
+            journal.push(logged);
+        }
+        // 逆命令作用于修改后的谱面, 其下标都已确定; 它的改动反过来就是这次的改动.
+        let start = self.changes.len();
+        inversed.changes(chart, &mut self.changes);
+        self.changes[start..]
+            .iter_mut()
+            .for_each(|change| *change = change.inverted());
+        Ok(inversed)
+    }
+}
+
+/// 将 [`EditHistory::take_journal`] 记录的命令按顺序重新应用到谱面上.
+pub fn replay(journal: impl IntoIterator<Item = ChartCommands>, chart: &mut Chart) -> Result<()> {
+    journal
+        .into_iter()
+        .try_for_each(|command| command.apply(chart).map(drop))
+}
+
+fn squash_description(mut descriptions: Vec<Cow<'static, str>>) -> Cow<'static, str> {
+    descriptions.dedup();
+    match descriptions.len() {
+        1 => descriptions.pop().unwrap(),
+        len => format!("{} (+{} more)", descriptions[0], len - 1).into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        fixture::{self, snapshot},
+        prelude::*,
+    };
+    use commands::{ChangeNoteTime, EditPoint};
+
+    fn move_point(x: f32) -> EditPoint {
+        EditPoint {
+            line_path: LinePath(0),
+            point_idx: 1,
+            new_time: None,
+            new_x: Some(x),
+            new_canvas: None,
+            new_color: None,
+            new_easing: None,
+            new_style: None,
+        }
+    }
+
+    #[test]
+    fn test_coalesce_in_gesture() {
+        let mut chart = fixture::zigzag();
+        let mut history = EditHistory::default();
+        history.set_coalesce_window(Duration::ZERO);
+        history.begin_gesture();
+        for x in [1., 2., 3.] {
+            history.push(move_point(x), &mut chart).unwrap();
+        }
+        history.end_gesture();
+        history
+            .push(
+                ChangeNoteTime {
+                    modify_to: 1.5,
+                    note_path: NotePath::new(0, 0),
+                },
+                &mut chart,
+            )
+            .unwrap();
+        assert_eq!(history.history_descriptions().len(), 2);
+        assert_eq!(history.history_descriptions()[0], "Edit point 1 on line 0");
+        history.undo(&mut chart).unwrap();
+        history.undo(&mut chart).unwrap();
+        assert_eq!(chart.lines[0].points.points()[1].value, 10.);
+        assert_eq!(chart.lines[0].notes[0].time, 1.);
+    }
+
+    #[test]
+    fn test_coalesce_window() {
+        let mut chart = fixture::zigzag();
+        let mut history = EditHistory::default();
+        history.set_coalesce_window(Duration::from_millis(20));
+        history.push(move_point(1.), &mut chart).unwrap();
+        std::thread::sleep(Duration::from_millis(40));
+        history.push(move_point(2.), &mut chart).unwrap();
+        // 窗口内的编辑仍会合并.
+        history.push(move_point(3.), &mut chart).unwrap();
+        assert_eq!(history.history_descriptions().len(), 2);
+        history.undo(&mut chart).unwrap();
+        assert_eq!(chart.lines[0].points.points()[1].value, 1.);
+        history.undo(&mut chart).unwrap();
+        assert_eq!(chart.lines[0].points.points()[1].value, 10.);
+    }
+
+    fn invalid_point() -> EditPoint {
+        EditPoint {
+            point_idx: 42,
+            ..move_point(0.)
+        }
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let mut chart = fixture::zigzag();
+        let original = snapshot(&chart);
+        let mut history = EditHistory::default();
+        let result = history.transaction(&mut chart, |tx| {
+            tx.apply(move_point(3.))?;
+            tx.apply(commands::InsertNote {
+                note: Note::new(0.5, NoteKind::Drag),
+                line: LinePath(0),
+                at: Some(0),
+            })?;
+            tx.apply(commands::RemovePoint {
+                line_path: LinePath(0),
+                point_idx: 2,
+            })?;
+            assert_eq!(tx.chart().lines[0].notes.len(), 2);
+            tx.apply(invalid_point())
+        });
+        assert!(matches!(
+            result,
+            Err(ChartConflictError::NoSuchPoint { point: 42, .. })
+        ));
+        assert_eq!(snapshot(&chart), original);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut chart = fixture::zigzag();
+        let mut history = EditHistory::default();
+        history
+            .push(
+                commands::InsertPoint {
+                    line_path: LinePath(0),
+                    point_idx: None,
+                    point: chart.lines[0].points.points()[2].clone(),
+                },
+                &mut chart,
+            )
+            .unwrap();
+        history.undo(&mut chart).unwrap();
+        let point = ChangeTarget::Point(chart_path::LinePointPath(LinePath(0), 3));
+        assert_eq!(
+            history.take_changes(),
+            [ChartChange::inserted(point), ChartChange::removed(point)]
+        );
+        assert!(history.take_changes().is_empty());
+    }
+
+    #[test]
+    fn test_transaction_commit() {
+        let mut chart = fixture::zigzag();
+        let original = snapshot(&chart);
+        let mut history = EditHistory::default();
+        history
+            .transaction(&mut chart, |tx| {
+                tx.apply(move_point(3.))?;
+                tx.apply(commands::RemoveLine {
+                    line_path: LinePath(0),
+                })
+            })
+            .unwrap();
+        assert!(chart.lines.is_empty());
+        assert_eq!(history.history_descriptions().len(), 1);
+        history.undo(&mut chart).unwrap();
+        assert_eq!(snapshot(&chart), original);
+    }
+
+    #[test]
+    fn test_undo_tree_branches() {
+        let mut chart = fixture::zigzag();
+        let mut history = EditHistory::default();
+        history.set_coalesce_window(Duration::ZERO);
+        history.push(move_point(1.), &mut chart).unwrap();
+        let first = history.current();
+        history.undo(&mut chart).unwrap();
+        history.push(move_point(2.), &mut chart).unwrap();
+        let second = history.current();
+        history.push(move_point(3.), &mut chart).unwrap();
+        assert_eq!(history.nodes()[history.root()].children(), [first, second]);
+
+        history.undo(&mut chart).unwrap();
+        history.switch_branch(-1, &mut chart).unwrap();
+        assert_eq!(history.current(), first);
+        assert_eq!(chart.lines[0].points.points()[1].value, 1.);
+
+        history.switch_branch(1, &mut chart).unwrap();
+        history.redo(&mut chart).unwrap();
+        assert_eq!(chart.lines[0].points.points()[1].value, 3.);
+        assert_eq!(history.history_descriptions().len(), 2);
+
+        history.jump_to(history.root(), &mut chart).unwrap();
+        assert_eq!(chart.lines[0].points.points()[1].value, 10.);
+        assert!(!history.can_undo());
+        assert_eq!(history.gen_redo_descriptions().count(), 2);
+    }
+
+    #[test]
+    fn test_failed_undo_keeps_history() {
+        let mut chart = fixture::zigzag();
+        let mut history = EditHistory::default();
+        history.push(move_point(3.), &mut chart).unwrap();
+        let edited = history.current();
+        let line = chart.lines.pop().unwrap();
+        assert!(history.undo(&mut chart).is_err());
+        assert_eq!(history.current(), edited);
+
+        chart.lines.push(line);
+        history.undo(&mut chart).unwrap();
+        assert_eq!(chart.lines[0].points.points()[1].value, 10.);
+
+        let line = chart.lines.pop().unwrap();
+        assert!(history.redo(&mut chart).is_err());
+        assert_eq!(history.current(), history.root());
+        chart.lines.push(line);
+        history.redo(&mut chart).unwrap();
+        assert_eq!(chart.lines[0].points.points()[1].value, 3.);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_journal_replay() {
+        let original = fixture::zigzag();
+        let mut chart = original.clone();
+        let mut history = EditHistory::default();
+        history.set_journaling(true);
+        history.push(move_point(5.), &mut chart).unwrap();
+        history.push_preedit(move_point(7.), &mut chart).unwrap();
+        history.undo(&mut chart).unwrap();
+        history.redo(&mut chart).unwrap();
+
+        let journal = serde_json::to_string(&history.take_journal()).unwrap();
+        let mut replayed = original;
+        replay(
+            serde_json::from_str::<Vec<ChartCommands>>(&journal).unwrap(),
+            &mut replayed,
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&replayed).unwrap(),
+            serde_json::to_value(&chart).unwrap()
+        );
+
+        let history: EditHistory =
+            serde_json::from_str(&serde_json::to_string(&history).unwrap()).unwrap();
+        assert_eq!(history.history_descriptions(), ["Edit point 1 on line 0"]);
+    }
+}
@@ -0,0 +1,82 @@
+use super::chart_path::{LinePath, LinePointPath, NotePath};
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+
+/// 一条命令对谱面造成的一处改动.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct ChartChange {
+    pub target: ChangeTarget,
+    pub kind: ChangeKind,
+}
+
+/// 被改动的对象.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub enum ChangeTarget {
+    /// 整条线, 或线上除点和音符外的数据 (如颜色).
+    Line(LinePath),
+    Point(LinePointPath),
+    Note(NotePath),
+    Canvas(usize),
+    Bpm,
+    CamScale,
+    CamMove,
+    ThemeControl,
+    Themes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub enum ChangeKind {
+    Modified,
+    /// 插入后, 同一容器中其后的对象下标加一.
+    Inserted,
+    /// 删除后, 同一容器中其后的对象下标减一.
+    Removed,
+}
+
+impl ChartChange {
+    pub const fn modified(target: ChangeTarget) -> Self {
+        Self {
+            target,
+            kind: ChangeKind::Modified,
+        }
+    }
+
+    pub const fn inserted(target: ChangeTarget) -> Self {
+        Self {
+            target,
+            kind: ChangeKind::Inserted,
+        }
+    }
+
+    pub const fn removed(target: ChangeTarget) -> Self {
+        Self {
+            target,
+            kind: ChangeKind::Removed,
+        }
+    }
+
+    /// 撤销这处改动时的改动.
+    pub const fn inverted(self) -> Self {
+        Self {
+            target: self.target,
+            kind: match self.kind {
+                ChangeKind::Modified => ChangeKind::Modified,
+                ChangeKind::Inserted => ChangeKind::Removed,
+                ChangeKind::Removed => ChangeKind::Inserted,
+            },
+        }
+    }
+
+    /// 这处改动是否会影响 [`crate::chart::ChartCache`].
+    pub const fn affects_cache(&self) -> bool {
+        matches!(self.target, ChangeTarget::Canvas(_) | ChangeTarget::Bpm)
+    }
+}
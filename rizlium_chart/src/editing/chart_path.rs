@@ -2,6 +2,11 @@ use crate::prelude::{Chart, KeyPoint, Line, LinePointData, Note};
 
 use super::{ChartConflictError, Result};
 
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+
 pub trait ChartPath {
     type Out;
     fn get<'c>(&self, chart: &'c Chart) -> Result<&'c Self::Out>;
@@ -10,7 +15,9 @@ pub trait ChartPath {
     fn valid(&self, chart: &Chart) -> Result<()>;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct NotePath(pub LinePath, pub usize);
 
 impl NotePath {
@@ -58,7 +65,9 @@ impl From<(usize, usize)> for NotePath {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct LinePath(pub usize);
 
 impl ChartPath for LinePath {
@@ -96,7 +105,9 @@ impl From<usize> for LinePath {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct LinePointPath(pub LinePath, pub usize);
 
 impl ChartPath for LinePointPath {
@@ -1,24 +1,44 @@
 use std::{any::type_name, borrow::Cow, fmt::Debug};
 
-use super::Result;
+use super::{ChartChange, Result};
 use crate::prelude::Chart;
 use enum_dispatch::enum_dispatch;
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+use tracing::error;
+
 mod note;
 pub use note::*;
 mod lines;
 pub use lines::*;
+mod transform;
+pub use transform::*;
+mod quantize;
+pub use quantize::*;
 
 #[enum_dispatch(ChartCommand)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub enum ChartCommands {
     ChangeNoteTime,
     InsertNote,
     RemoveNote,
+    SetNoteProperties,
     InsertLine,
     RemoveLine,
     InsertPoint,
     EditPoint,
     RemovePoint,
+    SplitLine,
+    JoinLines,
+    DuplicateLine,
+    SetLineSpeed,
+    TransformChart,
+    ReplaceChartParts,
+    Quantize,
     CommandSequence,
     Nop,
 }
@@ -27,37 +47,84 @@ pub enum ChartCommands {
 pub trait ChartCommand: Debug {
     fn apply(self, chart: &mut Chart) -> Result<ChartCommands>;
     fn validate(&self, chart: &Chart) -> Result<()>;
+    /// 将此命令应用到 `chart` 上时会造成的改动追加到 `out`.
+    ///
+    /// `chart` 是应用之前的谱面, 用于确定未指定的插入位置.
+    fn changes(&self, chart: &Chart, out: &mut Vec<ChartChange>);
     fn description(&self) -> Cow<'static, str> {
         type_name::<Self>().into()
     }
+    /// 尝试把紧随其后的逆命令 `later` 并入 `self`, 使两次编辑在历史中只占一项.
+    ///
+    /// `self` 和 `later` 都是 [`ChartCommand::apply`] 返回的逆命令, 其中 `later` 撤销较晚的那次编辑.
+    /// 合并成功后, 应用 `self` 的效果应等同于依次应用 `later` 和原来的 `self`.
+    ///
+    /// 默认不合并.
+    fn merge(&mut self, _later: &ChartCommands) -> bool {
+        false
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct CommandSequence {
     pub commands: Vec<ChartCommands>,
 }
 
 impl ChartCommand for CommandSequence {
+    /// 任意一条命令失败时, 已应用的命令会被撤销, 谱面保持原样.
     fn apply(self, chart: &mut Chart) -> Result<ChartCommands> {
-        Ok(Self {
-            commands: self
-                .commands
-                .into_iter()
-                // reverse to ensure inversed commands get processed in the correct order
-                .rev()
-                .map(|command| command.apply(chart))
-                .collect::<Result<Vec<_>>>()?,
+        let mut inversed = Vec::with_capacity(self.commands.len());
+        // reverse to ensure inversed commands get processed in the correct order
+        for command in self.commands.into_iter().rev() {
+            match command.apply(chart) {
+                Ok(inverse) => inversed.push(inverse),
+                Err(err) => {
+                    rollback(inversed, chart);
+                    return Err(err);
+                }
+            }
         }
-        .into())
+        Ok(Self { commands: inversed }.into())
     }
+    /// 只验证最先应用的命令. 之后的命令依赖前面命令的结果, 无法在原谱面上验证,
+    /// 它们失败时由 [`apply`](ChartCommand::apply) 回滚.
     fn validate(&self, chart: &Chart) -> Result<()> {
+        self.commands
+            .last()
+            .map_or(Ok(()), |command| command.validate(chart))
+    }
+    /// 子命令按应用顺序依次报告, 均以应用整个序列之前的谱面为准.
+    fn changes(&self, chart: &Chart, out: &mut Vec<ChartChange>) {
         self.commands
             .iter()
-            .try_for_each(|command| command.validate(chart))
+            .rev()
+            .for_each(|command| command.changes(chart, out));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        match self.commands.as_slice() {
+            [] => "Nothing".into(),
+            [command] => command.description(),
+            commands => format!("{} edits", commands.len()).into(),
+        }
     }
 }
 
-#[derive(Debug)]
+/// 以与应用顺序相反的顺序应用 `inverses`, 撤销已应用的命令.
+///
+/// 逆命令总应成功; 若失败则只能记录错误并继续.
+pub(crate) fn rollback(inverses: Vec<ChartCommands>, chart: &mut Chart) {
+    for inverse in inverses.into_iter().rev() {
+        if let Err(err) = inverse.apply(chart) {
+            error!("failed to roll back command: {err:?}");
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct Nop;
 
 impl ChartCommand for Nop {
@@ -67,4 +134,39 @@ impl ChartCommand for Nop {
     fn validate(&self, _chart: &Chart) -> Result<()> {
         Ok(())
     }
+    fn changes(&self, _chart: &Chart, _out: &mut Vec<ChartChange>) {}
+    fn description(&self) -> Cow<'static, str> {
+        "Nothing".into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        editing::chart_path::LinePath,
+        fixture::{self, snapshot},
+    };
+
+    #[test]
+    fn test_sequence_rolls_back() {
+        let mut chart = fixture::zigzag();
+        let original = snapshot(&chart);
+        let move_point = |point_idx| EditPoint {
+            line_path: LinePath(0),
+            point_idx,
+            new_time: None,
+            new_x: Some(3.),
+            new_canvas: None,
+            new_color: None,
+            new_easing: None,
+            new_style: None,
+        };
+        // 序列按相反顺序应用, 失败的命令放在最前面使其最后执行
+        let sequence = CommandSequence {
+            commands: vec![move_point(42).into(), move_point(1).into()],
+        };
+        assert!(sequence.apply(&mut chart).is_err());
+        assert_eq!(snapshot(&chart), original);
+    }
 }
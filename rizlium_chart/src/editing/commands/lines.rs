@@ -1,15 +1,22 @@
-use std::mem::replace;
+use std::{borrow::Cow, mem::replace};
 
 use crate::{
     editing::{
-        chart_path::{ChartPath, LinePath},
-        ChartConflictError,
+        chart_path::{ChartPath, LinePath, LinePointPath},
+        ChangeTarget, ChartChange, ChartConflictError,
     },
     prelude::*,
 };
 
-use super::ChartCommand;
-#[derive(Debug)]
+use super::{ChartCommand, ChartCommands, CommandSequence, ReplaceChartParts};
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct InsertLine {
     pub line: Line,
     pub at: Option<usize>,
@@ -29,9 +36,19 @@ impl ChartCommand for InsertLine {
     fn validate(&self, _chart: &Chart) -> crate::editing::Result<()> {
         Ok(())
     }
+    fn changes(&self, chart: &Chart, out: &mut Vec<ChartChange>) {
+        let len = chart.lines.len();
+        let at = self.at.unwrap_or(len).min(len);
+        out.push(ChartChange::inserted(ChangeTarget::Line(at.into())));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        "Insert line".into()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct RemoveLine {
     pub line_path: LinePath,
 }
@@ -48,9 +65,17 @@ impl ChartCommand for RemoveLine {
     fn validate(&self, chart: &Chart) -> crate::editing::Result<()> {
         self.line_path.valid(chart)
     }
+    fn changes(&self, _chart: &Chart, out: &mut Vec<ChartChange>) {
+        out.push(ChartChange::removed(ChangeTarget::Line(self.line_path)));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        format!("Remove line {}", self.line_path.0).into()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct EditPoint {
     pub line_path: LinePath,
     pub point_idx: usize,
@@ -59,6 +84,7 @@ pub struct EditPoint {
     pub new_canvas: Option<usize>,
     pub new_color: Option<ColorRGBA>,
     pub new_easing: Option<EasingId>,
+    pub new_style: Option<LineStyle>,
 }
 
 impl ChartCommand for EditPoint {
@@ -99,6 +125,9 @@ impl ChartCommand for EditPoint {
         let old_color = self
             .new_color
             .map(|color| replace(&mut point.relevant.color, color));
+        let old_style = self
+            .new_style
+            .map(|style| replace(&mut point.relevant.style, style));
         Ok(Self {
             line_path: self.line_path,
             point_idx: self.point_idx,
@@ -109,6 +138,7 @@ impl ChartCommand for EditPoint {
             new_easing: self
                 .new_easing
                 .map(|new| replace(&mut point.ease_type, new)),
+            new_style: old_style,
         }
         .into())
     }
@@ -129,9 +159,36 @@ impl ChartCommand for EditPoint {
             Ok(())
         }
     }
+    fn changes(&self, _chart: &Chart, out: &mut Vec<ChartChange>) {
+        out.push(ChartChange::modified(ChangeTarget::Point(LinePointPath(
+            self.line_path,
+            self.point_idx,
+        ))));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        format!("Edit point {} on line {}", self.point_idx, self.line_path.0).into()
+    }
+    /// 同一个点上的连续编辑: 已记录的原始值优先, 其余字段取较晚一次编辑记录的值.
+    fn merge(&mut self, later: &ChartCommands) -> bool {
+        let ChartCommands::EditPoint(later) = later else {
+            return false;
+        };
+        if later.line_path != self.line_path || later.point_idx != self.point_idx {
+            return false;
+        }
+        self.new_time = self.new_time.or(later.new_time);
+        self.new_x = self.new_x.or(later.new_x);
+        self.new_canvas = self.new_canvas.or(later.new_canvas);
+        self.new_color = self.new_color.or(later.new_color);
+        self.new_easing = self.new_easing.or(later.new_easing);
+        self.new_style = self.new_style.or(later.new_style);
+        true
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct InsertPoint {
     pub line_path: LinePath,
     pub point_idx: Option<usize>,
@@ -175,9 +232,25 @@ impl ChartCommand for InsertPoint {
     fn validate(&self, chart: &Chart) -> crate::editing::Result<()> {
         self.line_path.valid(chart)
     }
+    fn changes(&self, chart: &Chart, out: &mut Vec<ChartChange>) {
+        let len = self
+            .line_path
+            .get(chart)
+            .map_or(0, |line| line.points.len());
+        let at = self.point_idx.unwrap_or(len).min(len);
+        out.push(ChartChange::inserted(ChangeTarget::Point(LinePointPath(
+            self.line_path,
+            at,
+        ))));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        format!("Insert point on line {}", self.line_path.0).into()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct RemovePoint {
     pub line_path: LinePath,
     pub point_idx: usize,
@@ -212,4 +285,418 @@ impl ChartCommand for RemovePoint {
             Ok(())
         }
     }
+    fn changes(&self, _chart: &Chart, out: &mut Vec<ChartChange>) {
+        out.push(ChartChange::removed(ChangeTarget::Point(LinePointPath(
+            self.line_path,
+            self.point_idx,
+        ))));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        format!(
+            "Remove point {} on line {}",
+            self.point_idx, self.line_path.0
+        )
+        .into()
+    }
+}
+
+/// 在 `time` 处把 `spline` 分为两段, 两段在切点处各有一个点.
+fn split_spline<T: Tween, R: Clone>(
+    spline: &Spline<T, R>,
+    time: f32,
+) -> (Spline<T, R>, Spline<T, R>) {
+    let Some(value) = spline.value_padding(time) else {
+        return (Spline::default(), Spline::default());
+    };
+    let at = spline.points.partition_point(|point| point.time <= time);
+    let (before, after) = spline.points.split_at(at);
+    let Some(template) = before.last().or(after.first()) else {
+        return (Spline::default(), Spline::default());
+    };
+    let cut = KeyPoint {
+        time,
+        value,
+        ease_type: template.ease_type,
+        relevant: template.relevant.clone(),
+    };
+    let mut first = before.to_vec();
+    if first.last().is_none_or(|point| point.time < time) {
+        first.push(cut.clone());
+    }
+    let second = std::iter::once(cut).chain(after.iter().cloned()).collect();
+    (Spline { points: first }, Spline { points: second })
+}
+
+/// 把 `second` 接到 `first` 之后, `first` 中不早于 `second` 起点的点会被丢弃.
+fn join_spline<T: Tween, R: Clone>(first: &mut Spline<T, R>, second: &Spline<T, R>) {
+    if let Some(start) = second.start_time() {
+        first.points.retain(|point| point.time < start);
+    }
+    first.points.extend(second.points.iter().cloned());
+}
+
+/// 在 `time` 处把线切为两条, 后半段作为新线插入到原线之后.
+///
+/// 两条线在切点处相接. 音符按开始时间分配, 跨过切点的长条留在前一条线上.
+/// 速度倍率按游戏时间取值, 两条线都保留原来的倍率.
+/// 带有非线性缓动的线段被切开后形状会略有不同.
+/// 切点与线两端的距离必须大于 [`LINE_CLAMP_MARGIN`], 否则切出的线太短.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct SplitLine {
+    pub line_path: LinePath,
+    pub time: f32,
+}
+
+impl ChartCommand for SplitLine {
+    fn apply(self, chart: &mut Chart) -> crate::editing::Result<super::ChartCommands> {
+        self.validate(chart)?;
+        let line = self.line_path.get_mut(chart)?;
+        let (points, second_points) = split_spline(&line.points, self.time);
+        let (ring_color, second_ring_color) = split_spline(&line.ring_color, self.time);
+        let (line_color, second_line_color) = split_spline(&line.line_color, self.time);
+        let (notes, second_notes) = line
+            .notes
+            .iter()
+            .cloned()
+            .partition(|note| note.time < self.time);
+        let speed = line.speed.clone();
+        let original = replace(
+            line,
+            Line {
+                points,
+                notes,
+                ring_color,
+                line_color,
+                speed,
+            },
+        );
+        chart.lines.insert(
+            self.line_path.0 + 1,
+            Line {
+                points: second_points,
+                notes: second_notes,
+                ring_color: second_ring_color,
+                line_color: second_line_color,
+                speed: original.speed.clone(),
+            },
+        );
+        Ok(CommandSequence {
+            commands: vec![
+                ReplaceChartParts {
+                    lines: vec![(self.line_path, original)],
+                    ..Default::default()
+                }
+                .into(),
+                RemoveLine {
+                    line_path: LinePath(self.line_path.0 + 1),
+                }
+                .into(),
+            ],
+        }
+        .into())
+    }
+    fn validate(&self, chart: &Chart) -> crate::editing::Result<()> {
+        let line = self.line_path.get(chart)?;
+        match (line.points.start_time(), line.points.end_time()) {
+            (Some(start), Some(end))
+                if start + LINE_CLAMP_MARGIN < self.time && self.time < end - LINE_CLAMP_MARGIN =>
+            {
+                Ok(())
+            }
+            _ => Err(ChartConflictError::InvalidSplitTime {
+                line_path: self.line_path,
+                time: self.time,
+            }),
+        }
+    }
+    fn changes(&self, _chart: &Chart, out: &mut Vec<ChartChange>) {
+        out.push(ChartChange::modified(ChangeTarget::Line(self.line_path)));
+        out.push(ChartChange::inserted(ChangeTarget::Line(LinePath(
+            self.line_path.0 + 1,
+        ))));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        format!("Split line {}", self.line_path.0).into()
+    }
+}
+
+/// 把 `second` 接到 `first` 的末尾, 并删除 `second`.
+///
+/// `second` 不能在 `first` 结束前开始. 二者在同一时间相接时, 以 `second` 的起点为准.
+/// 合并后的线沿用 `first` 的速度倍率.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct JoinLines {
+    pub first: LinePath,
+    pub second: LinePath,
+}
+
+impl ChartCommand for JoinLines {
+    fn apply(self, chart: &mut Chart) -> crate::editing::Result<super::ChartCommands> {
+        self.validate(chart)?;
+        let second = self.second.get(chart)?.clone();
+        let first = self.first.get_mut(chart)?;
+        let mut joined = first.clone();
+        join_spline(&mut joined.points, &second.points);
+        join_spline(&mut joined.ring_color, &second.ring_color);
+        join_spline(&mut joined.line_color, &second.line_color);
+        joined.notes.extend(second.notes);
+        joined.notes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let original = replace(first, joined);
+        let removed = self.second.remove(chart)?;
+        Ok(CommandSequence {
+            commands: vec![
+                ReplaceChartParts {
+                    lines: vec![(self.first, original)],
+                    ..Default::default()
+                }
+                .into(),
+                InsertLine {
+                    line: removed,
+                    at: Some(self.second.0),
+                }
+                .into(),
+            ],
+        }
+        .into())
+    }
+    fn validate(&self, chart: &Chart) -> crate::editing::Result<()> {
+        let first = self.first.get(chart)?;
+        let second = self.second.get(chart)?;
+        match (first.points.end_time(), second.points.start_time()) {
+            (Some(end), Some(start)) if self.first != self.second && end <= start => Ok(()),
+            _ => Err(ChartConflictError::InvalidJoin {
+                first: self.first,
+                second: self.second,
+            }),
+        }
+    }
+    fn changes(&self, _chart: &Chart, out: &mut Vec<ChartChange>) {
+        out.push(ChartChange::modified(ChangeTarget::Line(self.first)));
+        out.push(ChartChange::removed(ChangeTarget::Line(self.second)));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        format!("Join line {} to line {}", self.second.0, self.first.0).into()
+    }
+}
+
+/// 复制一条线, 并把副本在时间和横坐标上平移.
+///
+/// `at` 为 `None` 时副本插入到原线之后.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct DuplicateLine {
+    pub line_path: LinePath,
+    pub time_offset: f32,
+    pub x_offset: f32,
+    pub at: Option<usize>,
+}
+
+impl ChartCommand for DuplicateLine {
+    fn apply(self, chart: &mut Chart) -> crate::editing::Result<super::ChartCommands> {
+        let mut line = self.line_path.get(chart)?.clone();
+        for point in &mut line.points.points {
+            point.time += self.time_offset;
+            point.value += self.x_offset;
+        }
+        for note in &mut line.notes {
+            note.time += self.time_offset;
+            if let NoteKind::Hold { end } = &mut note.kind {
+                *end += self.time_offset;
+            }
+        }
+        for point in line
+            .ring_color
+            .points
+            .iter_mut()
+            .chain(&mut line.line_color.points)
+        {
+            point.time += self.time_offset;
+        }
+        for point in &mut line.speed.points {
+            point.time += self.time_offset;
+        }
+        InsertLine {
+            line,
+            at: Some(self.at.unwrap_or(self.line_path.0 + 1)),
+        }
+        .apply(chart)
+    }
+    fn validate(&self, chart: &Chart) -> crate::editing::Result<()> {
+        self.line_path.valid(chart)
+    }
+    fn changes(&self, chart: &Chart, out: &mut Vec<ChartChange>) {
+        let at = self
+            .at
+            .unwrap_or(self.line_path.0 + 1)
+            .min(chart.lines.len());
+        out.push(ChartChange::inserted(ChangeTarget::Line(at.into())));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        format!("Duplicate line {}", self.line_path.0).into()
+    }
+}
+
+/// 替换线的速度倍率, 空的 [`Spline`] 表示倍率为 1.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct SetLineSpeed {
+    pub line_path: LinePath,
+    pub speed: Spline<f32>,
+}
+
+impl ChartCommand for SetLineSpeed {
+    fn apply(self, chart: &mut Chart) -> crate::editing::Result<super::ChartCommands> {
+        let line = self.line_path.get_mut(chart)?;
+        Ok(Self {
+            line_path: self.line_path,
+            speed: replace(&mut line.speed, self.speed),
+        }
+        .into())
+    }
+    fn validate(&self, chart: &Chart) -> crate::editing::Result<()> {
+        self.line_path.valid(chart)
+    }
+    fn changes(&self, _chart: &Chart, out: &mut Vec<ChartChange>) {
+        out.push(ChartChange::modified(ChangeTarget::Line(self.line_path)));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        format!("Set speed of line {}", self.line_path.0).into()
+    }
+    /// 同一条线上的连续修改只需保留最初的倍率.
+    fn merge(&mut self, later: &ChartCommands) -> bool {
+        matches!(later, ChartCommands::SetLineSpeed(later) if later.line_path == self.line_path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        editing::EditHistory,
+        fixture::{self, snapshot},
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_split_and_join_lines() {
+        let mut chart = fixture::zigzag();
+        chart.lines[0].notes.push(Note::new(1.8, NoteKind::Drag));
+        let original = snapshot(&chart);
+        let mut history = EditHistory::default();
+        history.set_coalesce_window(Duration::ZERO);
+        history
+            .push(
+                SplitLine {
+                    line_path: LinePath(0),
+                    time: 1.5,
+                },
+                &mut chart,
+            )
+            .unwrap();
+        assert_eq!(chart.lines.len(), 2);
+        assert_eq!(chart.lines[0].points.points()[2].value, 0.);
+        assert_eq!(chart.lines[1].points.start_time(), Some(1.5));
+        assert_eq!(chart.lines[1].notes.len(), 1);
+
+        history
+            .push(
+                JoinLines {
+                    first: LinePath(0),
+                    second: LinePath(1),
+                },
+                &mut chart,
+            )
+            .unwrap();
+        assert_eq!(chart.lines.len(), 1);
+        assert_eq!(chart.lines[0].points.len(), 4);
+        assert_eq!(chart.lines[0].notes.len(), 2);
+
+        history.undo(&mut chart).unwrap();
+        history.undo(&mut chart).unwrap();
+        assert_eq!(snapshot(&chart), original);
+    }
+
+    #[test]
+    fn test_split_too_close_to_end() {
+        let mut chart = fixture::zigzag();
+        for time in [0.005, 1.995] {
+            let split = SplitLine {
+                line_path: LinePath(0),
+                time,
+            };
+            assert!(matches!(
+                split.validate(&chart),
+                Err(ChartConflictError::InvalidSplitTime { .. })
+            ));
+        }
+        // 过短的线取中点, 不会因为 clamp 的范围无效而 panic.
+        chart.lines[0].points.points.truncate(2);
+        chart.lines[0].points.points[1].time = 0.005;
+        let cache = ChartCache::from_chart(&chart);
+        let pos = chart.with_cache(&cache).line_pos_at_clamped(0, 1., 0.);
+        assert!(pos.is_some_and(|[x, _]| x.is_finite()));
+    }
+
+    #[test]
+    fn test_line_speed() {
+        let mut chart = fixture::zigzag();
+        chart.canvases[0].speed = Spline::constant_speed(1.);
+        let hash = chart.content_hash();
+        let cache = ChartCache::from_chart(&chart);
+        let y = |chart: &Chart, game_time| {
+            chart
+                .with_cache(&cache)
+                .pos_for_linepoint_at(0, 2, game_time)
+                .unwrap()[1]
+        };
+        let original = y(&chart, 0.5);
+        let speed = |points: &[(f32, f32)]| -> Spline<f32> {
+            points
+                .iter()
+                .map(|&(time, value)| KeyPoint {
+                    time,
+                    value,
+                    ease_type: EasingId::Start,
+                    relevant: (),
+                })
+                .collect()
+        };
+        let mut history = EditHistory::default();
+        history
+            .push(
+                SetLineSpeed {
+                    line_path: LinePath(0),
+                    speed: speed(&[(0., 2.)]),
+                },
+                &mut chart,
+            )
+            .unwrap();
+        assert_eq!(y(&chart, 0.5), original * 2.);
+        assert_ne!(chart.content_hash(), hash);
+        history.undo(&mut chart).unwrap();
+        assert_eq!(y(&chart, 0.5), original);
+        assert_eq!(chart.content_hash(), hash);
+
+        // 倍率按时间积分: 改变倍率不会让已经在屏幕上的点跳动.
+        chart.lines[0].speed = speed(&[(0., 1.), (1., 3.), (1.5, 0.5)]);
+        let plain =
+            |from, to| cache.canvas_y_at(0, to).unwrap() - cache.canvas_y_at(0, from).unwrap();
+        let expected = plain(0., 1.) + 3. * plain(1., 1.5) + 0.5 * plain(1.5, 2.);
+        assert!((y(&chart, 0.) - expected).abs() < 1e-4);
+        for time in [1., 1.5] {
+            assert!((y(&chart, time - 1e-3) - y(&chart, time + 1e-3)).abs() < 1e-2);
+        }
+        let chart = chart.with_cache(&cache);
+        for (from, to) in [(0., 2.), (0.2, 1.2), (1.8, 0.4)] {
+            let distance = chart.line_distance(0, 0, from, to).unwrap();
+            let time = chart.line_distance_to_time(0, 0, from, distance).unwrap();
+            assert!((time - to).abs() < 1e-3);
+        }
+    }
 }
@@ -1,13 +1,22 @@
+use std::borrow::Cow;
+
 use crate::editing::chart_path::{ChartPath, LinePath};
-use crate::prelude::{Chart, Note};
+use crate::prelude::{Chart, Note, NoteProperties};
 
 use crate::editing::{
     chart_path::NotePath,
     commands::{ChartCommand, ChartCommands},
-    Result,
+    ChangeTarget, ChartChange, Result,
 };
 
-#[derive(Debug)]
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct ChangeNoteTime {
     pub modify_to: f32,
     pub note_path: NotePath,
@@ -27,9 +36,22 @@ impl ChartCommand for ChangeNoteTime {
     fn validate(&self, chart: &Chart) -> Result<()> {
         self.note_path.valid(chart)
     }
+    fn changes(&self, _chart: &Chart, out: &mut Vec<ChartChange>) {
+        out.push(ChartChange::modified(ChangeTarget::Note(self.note_path)));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        let NotePath(LinePath(line), note) = self.note_path;
+        format!("Change time of note {note} on line {line}").into()
+    }
+    /// 同一音符上的连续修改只需保留最早的原始时间.
+    fn merge(&mut self, later: &ChartCommands) -> bool {
+        matches!(later, ChartCommands::ChangeNoteTime(later) if later.note_path == self.note_path)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct InsertNote {
     pub note: Note,
     pub line: LinePath,
@@ -50,9 +72,21 @@ impl ChartCommand for InsertNote {
     fn validate(&self, chart: &Chart) -> Result<()> {
         self.line.valid(chart)
     }
+    fn changes(&self, chart: &Chart, out: &mut Vec<ChartChange>) {
+        let len = self.line.get(chart).map_or(0, |line| line.notes.len());
+        let at = self.at.unwrap_or(len).min(len);
+        out.push(ChartChange::inserted(ChangeTarget::Note(NotePath(
+            self.line, at,
+        ))));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        format!("Insert note on line {}", self.line.0).into()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
 pub struct RemoveNote {
     pub note_path: NotePath,
 }
@@ -74,5 +108,46 @@ impl ChartCommand for RemoveNote {
     fn validate(&self, chart: &Chart) -> Result<()> {
         self.note_path.valid(chart)
     }
+    fn changes(&self, _chart: &Chart, out: &mut Vec<ChartChange>) {
+        out.push(ChartChange::removed(ChangeTarget::Note(self.note_path)));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        let NotePath(LinePath(line), note) = self.note_path;
+        format!("Remove note {note} on line {line}").into()
+    }
 }
 // here used to have a test
+
+/// 替换音符的可选属性.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct SetNoteProperties {
+    pub note_path: NotePath,
+    pub properties: NoteProperties,
+}
+
+impl ChartCommand for SetNoteProperties {
+    fn apply(self, chart: &mut Chart) -> Result<ChartCommands> {
+        let note = self.note_path.get_mut(chart)?;
+        Ok(Self {
+            note_path: self.note_path,
+            properties: std::mem::replace(&mut note.properties, self.properties),
+        }
+        .into())
+    }
+    fn validate(&self, chart: &Chart) -> Result<()> {
+        self.note_path.valid(chart)
+    }
+    fn changes(&self, _chart: &Chart, out: &mut Vec<ChartChange>) {
+        out.push(ChartChange::modified(ChangeTarget::Note(self.note_path)));
+    }
+    fn description(&self) -> Cow<'static, str> {
+        let NotePath(LinePath(line), note) = self.note_path;
+        format!("Set properties of note {note} on line {line}").into()
+    }
+    /// 同一音符上的连续修改只需保留最初的属性.
+    fn merge(&mut self, later: &ChartCommands) -> bool {
+        matches!(later, ChartCommands::SetNoteProperties(later) if later.note_path == self.note_path)
+    }
+}
@@ -0,0 +1,234 @@
+use std::borrow::Cow;
+
+use crate::{
+    analysis::rhythm::nearest_grid,
+    editing::{
+        chart_path::{ChartPath, LinePath, LinePointPath},
+        ChangeTarget, ChartChange, ChartConflictError, NotePath,
+    },
+    prelude::*,
+};
+
+use super::{ChartCommand, ChartCommands, ReplaceChartParts};
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+
+/// 可以被量化的对象.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub enum QuantizeItem {
+    /// 音符, 长条的结尾也会一同量化.
+    Note(NotePath),
+    Point(LinePointPath),
+}
+
+impl QuantizeItem {
+    pub const fn line_path(&self) -> LinePath {
+        match self {
+            Self::Note(NotePath(line_path, _)) | Self::Point(LinePointPath(line_path, _)) => {
+                *line_path
+            }
+        }
+    }
+}
+
+/// 量化时一个对象的移动, 时间以 beat 为单位.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantizeMove {
+    pub item: QuantizeItem,
+    /// 移动的是否为长条的结尾.
+    pub hold_end: bool,
+    pub from: f32,
+    pub to: f32,
+}
+
+impl QuantizeMove {
+    /// 移动的实际时间, 以毫秒为单位.
+    pub fn millis(&self, cache: &ChartCache) -> f32 {
+        (cache.remap_beat(self.to) - cache.remap_beat(self.from)) * 1000.
+    }
+}
+
+/// 把对象吸附到最近的 1/`division` 拍上.
+///
+/// 网格见 [`nearest_grid`]. 线点不会越过相邻的点.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct Quantize {
+    pub items: Vec<QuantizeItem>,
+    pub division: u32,
+}
+
+type QuantizedLines = Vec<(LinePath, Line)>;
+
+impl Quantize {
+    /// 量化 `lines` 上的所有音符和线点.
+    pub fn lines(chart: &Chart, lines: impl IntoIterator<Item = LinePath>, division: u32) -> Self {
+        let items = lines
+            .into_iter()
+            .filter_map(|line_path| Some((line_path, line_path.get(chart).ok()?)))
+            .flat_map(|(line_path, line)| {
+                let notes =
+                    (0..line.notes.len()).map(move |i| QuantizeItem::Note(NotePath(line_path, i)));
+                let points = (0..line.points.len())
+                    .map(move |i| QuantizeItem::Point(LinePointPath(line_path, i)));
+                notes.chain(points)
+            })
+            .collect();
+        Self { items, division }
+    }
+
+    fn snap(&self, bpm: &Spline<f32>, time: f32) -> f32 {
+        nearest_grid(bpm, time, self.division)
+    }
+
+    /// 量化后的各条线, 以及每个对象的移动.
+    fn quantized(
+        &self,
+        chart: &Chart,
+    ) -> crate::editing::Result<(QuantizedLines, Vec<QuantizeMove>)> {
+        self.validate(chart)?;
+        let mut lines: Vec<(LinePath, Line)> = Vec::new();
+        let mut moves = Vec::with_capacity(self.items.len());
+        for item in &self.items {
+            let line_path = item.line_path();
+            let index = match lines.iter().position(|(path, _)| *path == line_path) {
+                Some(index) => index,
+                None => {
+                    lines.push((line_path, line_path.get(chart)?.clone()));
+                    lines.len() - 1
+                }
+            };
+            let line = &mut lines[index].1;
+            match *item {
+                QuantizeItem::Note(NotePath(_, note_idx)) => {
+                    let note = &mut line.notes[note_idx];
+                    let from = note.time;
+                    note.time = self.snap(&chart.bpm, from);
+                    moves.push(QuantizeMove {
+                        item: *item,
+                        hold_end: false,
+                        from,
+                        to: note.time,
+                    });
+                    if let NoteKind::Hold { end } = &mut note.kind {
+                        let from = *end;
+                        *end = self.snap(&chart.bpm, from).max(note.time);
+                        moves.push(QuantizeMove {
+                            item: *item,
+                            hold_end: true,
+                            from,
+                            to: *end,
+                        });
+                    }
+                }
+                QuantizeItem::Point(LinePointPath(_, point_idx)) => {
+                    let points = &mut line.points.points;
+                    let prev = point_idx
+                        .checked_sub(1)
+                        .map_or(f32::NEG_INFINITY, |i| points[i].time);
+                    let next = points.get(point_idx + 1).map_or(f32::INFINITY, |p| p.time);
+                    let point = &mut points[point_idx];
+                    let from = point.time;
+                    point.time = self.snap(&chart.bpm, from).clamp(prev, next);
+                    moves.push(QuantizeMove {
+                        item: *item,
+                        hold_end: false,
+                        from,
+                        to: point.time,
+                    });
+                }
+            }
+        }
+        Ok((lines, moves))
+    }
+
+    /// 应用到 `chart` 上时每个对象的移动.
+    pub fn moves(&self, chart: &Chart) -> crate::editing::Result<Vec<QuantizeMove>> {
+        Ok(self.quantized(chart)?.1)
+    }
+}
+
+impl ChartCommand for Quantize {
+    fn apply(self, chart: &mut Chart) -> crate::editing::Result<ChartCommands> {
+        let (lines, _) = self.quantized(chart)?;
+        ReplaceChartParts {
+            lines,
+            ..Default::default()
+        }
+        .apply(chart)
+    }
+    fn validate(&self, chart: &Chart) -> crate::editing::Result<()> {
+        if self.division == 0 {
+            return Err(ChartConflictError::InvalidTransform {
+                reason: "division must be positive",
+            });
+        }
+        self.items.iter().try_for_each(|item| match item {
+            QuantizeItem::Note(note_path) => note_path.valid(chart),
+            QuantizeItem::Point(point_path) => point_path.valid(chart),
+        })
+    }
+    fn changes(&self, _chart: &Chart, out: &mut Vec<ChartChange>) {
+        let mut lines: Vec<LinePath> = Vec::new();
+        for item in &self.items {
+            if !lines.contains(&item.line_path()) {
+                lines.push(item.line_path());
+            }
+        }
+        out.extend(
+            lines
+                .into_iter()
+                .map(|line_path| ChartChange::modified(ChangeTarget::Line(line_path))),
+        );
+    }
+    fn description(&self) -> Cow<'static, str> {
+        format!(
+            "Quantize {} items to 1/{} beat",
+            self.items.len(),
+            self.division
+        )
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        editing::EditHistory,
+        fixture::{self, snapshot},
+    };
+
+    #[test]
+    fn test_quantize() {
+        let mut chart = fixture::zigzag();
+        chart.lines[0].notes[0].time = 1.1;
+        chart.lines[0]
+            .notes
+            .push(Note::new(0.3, NoteKind::Hold { end: 1.7 }));
+        let original = snapshot(&chart);
+        let quantize = Quantize::lines(&chart, [LinePath(0)], 4);
+        let moves = quantize.moves(&chart).unwrap();
+        let cache = ChartCache::from_chart(&chart);
+        assert!((moves[0].millis(&cache) + 50.).abs() < 1e-3);
+
+        let mut history = EditHistory::default();
+        history.push(quantize, &mut chart).unwrap();
+        assert_eq!(chart.lines[0].notes[0].time, 1.);
+        assert!(matches!(
+            chart.lines[0].notes[1],
+            Note {
+                time: 0.25,
+                kind: NoteKind::Hold { end: 1.75 },
+                ..
+            }
+        ));
+        history.undo(&mut chart).unwrap();
+        assert_eq!(snapshot(&chart), original);
+    }
+}
@@ -0,0 +1,458 @@
+use std::{borrow::Cow, mem::swap};
+
+use crate::{
+    editing::{
+        chart_path::{ChartPath, LinePath},
+        ChangeTarget, ChartChange, ChartConflictError,
+    },
+    prelude::*,
+};
+
+use super::{ChartCommand, ChartCommands};
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+
+/// 用给定的内容替换谱面的一部分, 逆命令为被替换下来的内容.
+///
+/// 用作变换类命令的逆命令, 保证撤销后与原谱面完全一致.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct ReplaceChartParts {
+    pub lines: Vec<(LinePath, Line)>,
+    pub canvases: Option<Vec<Canvas>>,
+    pub bpm: Option<Spline<f32>>,
+    pub cam_scale: Option<Spline<f32>>,
+    pub cam_move: Option<Spline<f32>>,
+    pub theme_control: Option<Spline<usize>>,
+}
+
+impl ChartCommand for ReplaceChartParts {
+    fn apply(mut self, chart: &mut Chart) -> crate::editing::Result<ChartCommands> {
+        self.validate(chart)?;
+        for (line_path, line) in &mut self.lines {
+            swap(line, line_path.get_mut(chart)?);
+        }
+        if let Some(canvases) = &mut self.canvases {
+            swap(canvases, &mut chart.canvases);
+        }
+        if let Some(bpm) = &mut self.bpm {
+            swap(bpm, &mut chart.bpm);
+        }
+        if let Some(cam_scale) = &mut self.cam_scale {
+            swap(cam_scale, &mut chart.cam_scale);
+        }
+        if let Some(cam_move) = &mut self.cam_move {
+            swap(cam_move, &mut chart.cam_move);
+        }
+        if let Some(theme_control) = &mut self.theme_control {
+            swap(theme_control, &mut chart.theme_control);
+        }
+        Ok(self.into())
+    }
+    fn validate(&self, chart: &Chart) -> crate::editing::Result<()> {
+        self.lines
+            .iter()
+            .try_for_each(|(line_path, _)| line_path.valid(chart))
+    }
+    fn changes(&self, chart: &Chart, out: &mut Vec<ChartChange>) {
+        out.extend(
+            self.lines
+                .iter()
+                .map(|(line_path, _)| ChartChange::modified(ChangeTarget::Line(*line_path))),
+        );
+        if let Some(canvases) = &self.canvases {
+            let (old, new) = (chart.canvases.len(), canvases.len());
+            out.extend((0..old.min(new)).map(|i| ChartChange::modified(ChangeTarget::Canvas(i))));
+            out.extend((old..new).map(|i| ChartChange::inserted(ChangeTarget::Canvas(i))));
+            out.extend(
+                (new..old)
+                    .rev()
+                    .map(|i| ChartChange::removed(ChangeTarget::Canvas(i))),
+            );
+        }
+        let singles = [
+            (self.bpm.is_some(), ChangeTarget::Bpm),
+            (self.cam_scale.is_some(), ChangeTarget::CamScale),
+            (self.cam_move.is_some(), ChangeTarget::CamMove),
+            (self.theme_control.is_some(), ChangeTarget::ThemeControl),
+        ];
+        out.extend(
+            singles
+                .into_iter()
+                .filter_map(|(replaced, target)| replaced.then_some(ChartChange::modified(target))),
+        );
+    }
+    fn description(&self) -> Cow<'static, str> {
+        "Replace chart parts".into()
+    }
+}
+
+/// 变换作用的范围.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub enum TransformScope {
+    /// 整个谱面, 包括画布, 镜头和主题控制.
+    Chart,
+    /// 指定的线. 画布由多条线共用, 因此不会被变换.
+    Lines(Vec<LinePath>),
+}
+
+/// 对谱面的整体变换. 时间均以 beat 为单位.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub enum ChartTransform {
+    /// 以 `axis` 为轴水平翻转.
+    Mirror { axis: f32 },
+    /// 所有对象的时间加上 `offset`. BPM 不变.
+    ShiftTime { offset: f32 },
+    /// 以 `pivot` 为中心, 把时间拉伸为原来的 `factor` 倍. BPM 不变.
+    StretchTime { pivot: f32, factor: f32 },
+    /// 以 `origin` 为中心, 把横坐标缩放为原来的 `factor` 倍.
+    ScaleX { origin: f32, factor: f32 },
+    /// 换用新的 BPM, 并调整所有对象的 beat 使其实际时间不变.
+    ///
+    /// 只能作用于整个谱面.
+    RemapBpm { bpm: Spline<f32> },
+}
+
+/// 把 [`ChartTransform`] 应用到 `scope` 内的所有线点, 音符, 颜色曲线以及画布上.
+///
+/// 对于 [`TransformScope::Lines`], 线点的横坐标是相对其画布变换的.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+pub struct TransformChart {
+    pub scope: TransformScope,
+    pub transform: ChartTransform,
+}
+
+enum TimeMap {
+    Keep,
+    Affine { scale: f32, offset: f32 },
+    Bpm { old: ChartCache, new: ChartCache },
+}
+
+impl TimeMap {
+    fn map(&self, time: f32) -> f32 {
+        match self {
+            Self::Keep => time,
+            Self::Affine { scale, offset } => time.mul_add(*scale, *offset),
+            Self::Bpm { old, new } => new.map_time(old.remap_beat(time)),
+        }
+    }
+
+    fn map_spline<T: Tween, R>(&self, spline: &mut Spline<T, R>) {
+        spline
+            .points
+            .iter_mut()
+            .for_each(|point| point.time = self.map(point.time));
+    }
+}
+
+impl ChartTransform {
+    fn time_map(&self, chart: &Chart) -> TimeMap {
+        match self {
+            Self::ShiftTime { offset } => TimeMap::Affine {
+                scale: 1.,
+                offset: *offset,
+            },
+            Self::StretchTime { pivot, factor } => TimeMap::Affine {
+                scale: *factor,
+                offset: pivot * (1. - factor),
+            },
+            Self::RemapBpm { bpm } => {
+                let mut new = ChartCache::default();
+                new.update_beat(bpm);
+                TimeMap::Bpm {
+                    old: ChartCache::from_chart(chart),
+                    new,
+                }
+            }
+            Self::Mirror { .. } | Self::ScaleX { .. } => TimeMap::Keep,
+        }
+    }
+
+    /// 横坐标变换 `x' = (x - origin) * factor + origin`, 以 `(origin, factor)` 表示.
+    fn x_map(&self) -> Option<(f32, f32)> {
+        match self {
+            Self::Mirror { axis } => Some((*axis, -1.)),
+            Self::ScaleX { origin, factor } => Some((*origin, *factor)),
+            _ => None,
+        }
+    }
+}
+
+fn transform_line(line: &mut Line, time: &TimeMap, x: Option<(f32, f32)>) {
+    time.map_spline(&mut line.points);
+    if let Some((origin, factor)) = x {
+        line.points
+            .points
+            .iter_mut()
+            .for_each(|point| point.value = (point.value - origin).mul_add(factor, origin));
+    }
+    for note in &mut line.notes {
+        note.time = time.map(note.time);
+        if let NoteKind::Hold { end } = &mut note.kind {
+            *end = time.map(*end);
+        }
+    }
+    time.map_spline(&mut line.ring_color);
+    time.map_spline(&mut line.line_color);
+    time.map_spline(&mut line.speed);
+}
+
+impl TransformChart {
+    /// 变换后的各部分, 应用后即得到变换后的谱面.
+    fn transformed(&self, chart: &Chart) -> ReplaceChartParts {
+        let time = self.transform.time_map(chart);
+        let x = self.transform.x_map();
+        let line_paths = match &self.scope {
+            TransformScope::Chart => (0..chart.lines.len()).map(LinePath).collect(),
+            TransformScope::Lines(lines) => lines.clone(),
+        };
+        let mut lines: Vec<(LinePath, Line)> = Vec::with_capacity(line_paths.len());
+        for line_path in line_paths {
+            // 重复的线只变换一次.
+            if lines.iter().any(|(path, _)| *path == line_path) {
+                continue;
+            }
+            let mut line = chart.lines[line_path.0].clone();
+            transform_line(&mut line, &time, x);
+            lines.push((line_path, line));
+        }
+        let mut parts = ReplaceChartParts {
+            lines,
+            ..Default::default()
+        };
+        if self.scope != TransformScope::Chart {
+            return parts;
+        }
+        let mut canvases = chart.canvases.clone();
+        for canvas in &mut canvases {
+            time.map_spline(&mut canvas.x_pos);
+            time.map_spline(&mut canvas.speed);
+            // 线点已绕 origin 变换, 画布只需缩放, 二者之和即为绕 origin 变换后的位置.
+            if let Some((_, factor)) = x {
+                canvas
+                    .x_pos
+                    .points
+                    .iter_mut()
+                    .for_each(|point| point.value *= factor);
+            }
+        }
+        parts.canvases = Some(canvases);
+        if let Some((_, factor)) = x {
+            // 镜头的横向移动与画布相同, 只需缩放.
+            let mut cam_move = chart.cam_move.clone();
+            cam_move
+                .points
+                .iter_mut()
+                .for_each(|point| point.value *= factor);
+            parts.cam_move = Some(cam_move);
+        }
+        if !matches!(time, TimeMap::Keep) {
+            let mut cam_scale = chart.cam_scale.clone();
+            let mut cam_move = chart.cam_move.clone();
+            let mut theme_control = chart.theme_control.clone();
+            time.map_spline(&mut cam_scale);
+            time.map_spline(&mut cam_move);
+            time.map_spline(&mut theme_control);
+            parts.cam_scale = Some(cam_scale);
+            parts.cam_move = Some(cam_move);
+            parts.theme_control = Some(theme_control);
+        }
+        if let ChartTransform::RemapBpm { bpm } = &self.transform {
+            parts.bpm = Some(bpm.clone());
+        }
+        parts
+    }
+}
+
+impl ChartCommand for TransformChart {
+    fn apply(self, chart: &mut Chart) -> crate::editing::Result<ChartCommands> {
+        self.validate(chart)?;
+        self.transformed(chart).apply(chart)
+    }
+    fn validate(&self, chart: &Chart) -> crate::editing::Result<()> {
+        let reason = match &self.transform {
+            ChartTransform::StretchTime { factor, .. } if !(factor.is_finite() && *factor > 0.) => {
+                Some("stretch factor must be positive")
+            }
+            ChartTransform::ScaleX { factor, .. } if !factor.is_finite() => {
+                Some("scale factor must be finite")
+            }
+            ChartTransform::RemapBpm { .. } if self.scope != TransformScope::Chart => {
+                Some("bpm can only be remapped for the whole chart")
+            }
+            ChartTransform::RemapBpm { bpm } if bpm.is_empty() => Some("bpm is empty"),
+            ChartTransform::RemapBpm { bpm } if bpm.iter().any(|point| point.value <= 0.) => {
+                Some("bpm must be positive")
+            }
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            return Err(ChartConflictError::InvalidTransform { reason });
+        }
+        if let TransformScope::Lines(lines) = &self.scope {
+            lines
+                .iter()
+                .try_for_each(|line_path| line_path.valid(chart))?;
+        }
+        Ok(())
+    }
+    fn changes(&self, chart: &Chart, out: &mut Vec<ChartChange>) {
+        match &self.scope {
+            TransformScope::Chart => {
+                out.extend(
+                    (0..chart.lines.len())
+                        .map(|i| ChartChange::modified(ChangeTarget::Line(LinePath(i)))),
+                );
+                out.extend(
+                    (0..chart.canvases.len())
+                        .map(|i| ChartChange::modified(ChangeTarget::Canvas(i))),
+                );
+                if self.transform.x_map().is_none() {
+                    out.extend(
+                        [
+                            ChangeTarget::CamScale,
+                            ChangeTarget::CamMove,
+                            ChangeTarget::ThemeControl,
+                        ]
+                        .map(ChartChange::modified),
+                    );
+                } else {
+                    out.push(ChartChange::modified(ChangeTarget::CamMove));
+                }
+                if matches!(self.transform, ChartTransform::RemapBpm { .. }) {
+                    out.push(ChartChange::modified(ChangeTarget::Bpm));
+                }
+            }
+            TransformScope::Lines(lines) => out.extend(
+                lines
+                    .iter()
+                    .map(|line_path| ChartChange::modified(ChangeTarget::Line(*line_path))),
+            ),
+        }
+    }
+    fn description(&self) -> Cow<'static, str> {
+        let action = match &self.transform {
+            ChartTransform::Mirror { .. } => "Mirror",
+            ChartTransform::ShiftTime { .. } => "Shift",
+            ChartTransform::StretchTime { .. } => "Stretch",
+            ChartTransform::ScaleX { .. } => "Scale",
+            ChartTransform::RemapBpm { .. } => "Remap bpm of",
+        };
+        match &self.scope {
+            TransformScope::Chart => format!("{action} chart"),
+            TransformScope::Lines(lines) if lines.len() == 1 => {
+                format!("{action} line {}", lines[0].0)
+            }
+            TransformScope::Lines(lines) => format!("{action} {} lines", lines.len()),
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        editing::EditHistory,
+        fixture::{self, snapshot},
+    };
+
+    #[test]
+    fn test_transform_undo_is_exact() {
+        let mut chart = fixture::zigzag();
+        chart.lines[0]
+            .notes
+            .push(Note::new(0.3, NoteKind::Hold { end: 1.7 }));
+        chart.cam_move = fixture::line([(0., 0.), (2., 100.)]).points.with_relevant();
+        let original = snapshot(&chart);
+        let mut history = EditHistory::default();
+        history
+            .push(
+                TransformChart {
+                    scope: TransformScope::Chart,
+                    transform: ChartTransform::StretchTime {
+                        pivot: 1.,
+                        factor: 0.3,
+                    },
+                },
+                &mut chart,
+            )
+            .unwrap();
+        assert_eq!(chart.lines[0].points.points()[2].time, 1.3);
+        assert!(matches!(
+            chart.lines[0].notes[1].kind,
+            NoteKind::Hold { end } if (end - 1.21).abs() < 1e-6
+        ));
+        history
+            .push(
+                TransformChart {
+                    scope: TransformScope::Lines(vec![LinePath(0)]),
+                    transform: ChartTransform::Mirror { axis: 1. },
+                },
+                &mut chart,
+            )
+            .unwrap();
+        assert_eq!(chart.lines[0].points.points()[1].value, -8.);
+        history.take_changes();
+        history
+            .push(
+                TransformChart {
+                    scope: TransformScope::Chart,
+                    transform: ChartTransform::Mirror { axis: 1. },
+                },
+                &mut chart,
+            )
+            .unwrap();
+        // 镜头跟随画布一起翻转.
+        assert_eq!(chart.cam_move.points()[1].value, -100.);
+        assert!(history
+            .take_changes()
+            .contains(&ChartChange::modified(ChangeTarget::CamMove)));
+        history.undo(&mut chart).unwrap();
+        history.undo(&mut chart).unwrap();
+        history.undo(&mut chart).unwrap();
+        assert_eq!(snapshot(&chart), original);
+    }
+
+    #[test]
+    fn test_remap_bpm_keeps_real_time() {
+        let mut chart = fixture::zigzag();
+        let old_cache = ChartCache::from_chart(&chart);
+        let real = old_cache.remap_beat(chart.lines[0].notes[0].time);
+        let bpm = Spline::from(vec![KeyPoint {
+            time: 0.,
+            value: 180.,
+            ease_type: EasingId::Start,
+            relevant: (),
+        }]);
+        TransformChart {
+            scope: TransformScope::Chart,
+            transform: ChartTransform::RemapBpm { bpm },
+        }
+        .apply(&mut chart)
+        .unwrap();
+        let new_cache = ChartCache::from_chart(&chart);
+        assert!((new_cache.remap_beat(chart.lines[0].notes[0].time) - real).abs() < 1e-4);
+        assert!((chart.lines[0].notes[0].time - 1.5).abs() < 1e-4);
+
+        let invalid = TransformChart {
+            scope: TransformScope::Lines(vec![LinePath(0)]),
+            transform: ChartTransform::RemapBpm {
+                bpm: chart.bpm.clone(),
+            },
+        };
+        assert!(matches!(
+            invalid.validate(&chart),
+            Err(ChartConflictError::InvalidTransform { .. })
+        ));
+    }
+}
@@ -0,0 +1,51 @@
+//! 测试用的谱面.
+use crate::chart::{
+    Canvas, Chart, EasingId, KeyPoint, Line, LinePointData, Note, NoteKind, Spline,
+};
+
+/// 一个画布, 120 BPM, 镜头不动.
+///
+/// 画布速度使用 `Start` 缓动, 因此画布不移动; 需要移动的测试可替换为 [`Spline::constant_speed`].
+pub fn chart(lines: Vec<Line>) -> Chart {
+    Chart {
+        themes: vec![],
+        theme_control: Spline::EMPTY,
+        lines,
+        canvases: vec![Canvas {
+            x_pos: Spline::constant(0.),
+            speed: Spline::constant(1.),
+        }],
+        bpm: Spline::constant(120.),
+        cam_scale: Spline::constant(1.),
+        cam_move: Spline::constant(0.),
+    }
+}
+
+/// 以 `Linear` 缓动依次经过 `(time, x)` 的线.
+pub fn line(points: impl IntoIterator<Item = (f32, f32)>) -> Line {
+    Line::from_iter(points.into_iter().map(|(time, value)| KeyPoint {
+        time,
+        value,
+        ease_type: EasingId::Linear,
+        relevant: LinePointData::default(),
+    }))
+}
+
+/// 用于比较谱面是否完全一致.
+pub fn snapshot(chart: &Chart) -> String {
+    format!("{chart:?}")
+}
+
+/// 一条经过 `(0, 0)`, `(1, 10)`, `(2, -10)` 的线, 第 1 拍上有一个 tap.
+pub fn zigzag() -> Chart {
+    let mut line = line([(0., 0.), (1., 10.), (2., -10.)]);
+    line.notes.push(Note::new(1., NoteKind::Tap));
+    chart(vec![line])
+}
+
+/// 一条不动的线上依次放置 `notes`.
+pub fn chart_with_notes(notes: impl IntoIterator<Item = Note>) -> Chart {
+    let mut line = line([(0., 0.), (16., 0.)]);
+    line.notes.extend(notes);
+    chart(vec![line])
+}
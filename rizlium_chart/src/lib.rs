@@ -13,6 +13,16 @@ pub mod runtime;
 #[cfg(feature = "editing")]
 pub mod editing;
 
+/// 谱面格式的 JSON Schema.
+#[cfg(feature = "schema")]
+pub mod schema;
+
+/// 谱面分析, 如节奏对齐与可玩性检查.
+pub mod analysis;
+
+#[cfg(test)]
+mod fixture;
+
 /// 正常情况下游戏画面截取的部分.
 pub const VIEW_RECT: [[f32; 2]; 2] = [[-450., 0.], [450., 1600.]];
 
@@ -1,10 +1,31 @@
 use snafu::prelude::*;
 
+pub mod diagnostic;
+use diagnostic::JsonPath;
+
 #[cfg(feature = "rizline")]
 pub mod rizline;
 
+#[cfg(feature = "midi")]
+pub mod midi;
+
+#[cfg(feature = "phigros")]
+pub mod phigros;
+
+#[cfg(feature = "lanes")]
+pub mod lanes;
+
+#[cfg(feature = "svg")]
+pub mod svg;
+
 #[derive(Debug, Snafu, Clone)]
 pub enum ConvertError {
+    /// 发生在原文件中 `path` 处的错误.
+    #[snafu(display("{path}: {source}"))]
+    At {
+        path: JsonPath,
+        source: Box<ConvertError>,
+    },
     #[snafu(display("No bpm data found"))]
     EmptyBPM,
     #[snafu(display("Hold at line {line_idx}, index {note_idx} has no end"))]
@@ -13,6 +34,51 @@ pub enum ConvertError {
     UnknownNoteKind { raw_kind: usize },
     #[snafu(display("Unknown ease kind: {raw_kind}"))]
     UnknownEaseKind { raw_kind: u8 },
+    #[snafu(display(
+        "Unsupported file version {version}, the latest supported version is {latest}"
+    ))]
+    UnsupportedFileVersion { version: i64, latest: i64 },
+    #[snafu(display("Invalid MIDI file: {message}"))]
+    InvalidMidi { message: String },
+    #[snafu(display("SMPTE timecode is not supported, only ticks per beat"))]
+    SmpteTiming,
+    #[snafu(display("MIDI ticks per beat is zero"))]
+    ZeroTicksPerBeat,
+    #[snafu(display("Track {track} is out of range, the file has {count} tracks"))]
+    TrackOutOfRange { track: usize, count: usize },
+    /// `line` 为 0 表示与具体的行无关.
+    #[snafu(display("Invalid lane chart at line {line}: {reason}"))]
+    InvalidLaneChart { line: usize, reason: &'static str },
+    #[snafu(display("Invalid SVG path data at {position}: {reason}"))]
+    InvalidSvgPath {
+        position: usize,
+        reason: &'static str,
+    },
+    #[snafu(display("No path found in SVG"))]
+    NoSvgPath,
+    #[snafu(display("Subpath {subpath} turns back in time and can not become a line"))]
+    NonMonotonicPath { subpath: usize },
+    #[snafu(display("Canvas {canvas} does not exist or its height can not be mapped to time"))]
+    CanvasUnavailable { canvas: usize },
 }
+/// 有损转换中被忽略或近似处理的内容.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertWarning {
+    /// 所在的线, `None` 表示与具体的线无关.
+    pub line_idx: Option<usize>,
+    pub feature: &'static str,
+    /// 出现的次数.
+    pub count: usize,
+}
+
+impl std::fmt::Display for ConvertWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line_idx {
+            Some(line_idx) => write!(f, "Line {line_idx}: {} (x{})", self.feature, self.count),
+            None => write!(f, "{} (x{})", self.feature, self.count),
+        }
+    }
+}
+
 #[allow(unused)]
 type ConvertResult<T, E = ConvertError> = std::result::Result<T, E>;
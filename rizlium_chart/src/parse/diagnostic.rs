@@ -0,0 +1,320 @@
+//! 带有 JSON 路径和行列号的谱面错误.
+use std::{borrow::Cow, fmt};
+
+#[cfg(feature = "deserialize")]
+use serde::de::DeserializeOwned;
+
+use super::ConvertError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(Cow<'static, str>),
+    Index(usize),
+}
+
+/// JSON 中的位置, 以 `lines[3].linePoints[17].easeType` 的形式显示.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JsonPath(pub Vec<PathSegment>);
+
+impl JsonPath {
+    pub fn key(&mut self, key: impl Into<Cow<'static, str>>) -> &mut Self {
+        self.0.push(PathSegment::Key(key.into()));
+        self
+    }
+    pub fn index(&mut self, index: usize) -> &mut Self {
+        self.0.push(PathSegment::Index(index));
+        self
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str(".");
+        }
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i == 0 => write!(f, "{key}")?,
+                PathSegment::Key(key) => write!(f, ".{key}")?,
+                PathSegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "deserialize")]
+impl From<&serde_path_to_error::Path> for JsonPath {
+    fn from(path: &serde_path_to_error::Path) -> Self {
+        use serde_path_to_error::Segment;
+        Self(
+            path.iter()
+                .map(|segment| match segment {
+                    Segment::Seq { index } => PathSegment::Index(*index),
+                    Segment::Map { key } => PathSegment::Key(key.clone().into()),
+                    Segment::Enum { variant } => PathSegment::Key(variant.clone().into()),
+                    Segment::Unknown => PathSegment::Key("?".into()),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// 只用于定位的 JSON 扫描器, 假定输入是合法的 JSON.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    fn peek(&mut self) -> Option<u8> {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+        self.bytes.get(self.pos).copied()
+    }
+    fn eat(&mut self, byte: u8) -> Option<()> {
+        (self.peek()? == byte).then(|| self.pos += 1)
+    }
+    /// 返回字符串未转义的内容.
+    fn string(&mut self) -> Option<&[u8]> {
+        self.eat(b'"')?;
+        let start = self.pos;
+        loop {
+            match *self.bytes.get(self.pos)? {
+                b'\\' => self.pos += 2,
+                b'"' => break,
+                _ => self.pos += 1,
+            }
+        }
+        self.pos += 1;
+        Some(&self.bytes[start..self.pos - 1])
+    }
+    fn skip_value(&mut self) -> Option<()> {
+        match self.peek()? {
+            b'"' => {
+                self.string()?;
+            }
+            open @ (b'{' | b'[') => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                self.pos += 1;
+                if self.eat(close).is_some() {
+                    return Some(());
+                }
+                loop {
+                    if open == b'{' {
+                        self.string()?;
+                        self.eat(b':')?;
+                    }
+                    self.skip_value()?;
+                    if self.eat(close).is_some() {
+                        break;
+                    }
+                    self.eat(b',')?;
+                }
+            }
+            _ => {
+                while self
+                    .bytes
+                    .get(self.pos)
+                    .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+                {
+                    self.pos += 1;
+                }
+            }
+        }
+        Some(())
+    }
+    /// 移动到当前值中 `segment` 所指的值的开头.
+    fn enter(&mut self, segment: &PathSegment) -> Option<()> {
+        match segment {
+            PathSegment::Key(key) => {
+                self.eat(b'{')?;
+                loop {
+                    let found = self.string()? == key.as_bytes();
+                    self.eat(b':')?;
+                    if found {
+                        self.peek()?;
+                        return Some(());
+                    }
+                    self.skip_value()?;
+                    self.eat(b',')?;
+                }
+            }
+            PathSegment::Index(index) => {
+                self.eat(b'[')?;
+                for _ in 0..*index {
+                    self.skip_value()?;
+                    self.eat(b',')?;
+                }
+                self.peek()?;
+                Some(())
+            }
+        }
+    }
+}
+
+/// `path` 所指的值在 `text` 中的行号和列号 (从 1 开始).
+///
+/// 包含转义字符的键不会被匹配.
+pub fn locate(text: &[u8], path: &JsonPath) -> Option<(usize, usize)> {
+    let mut scanner = Scanner {
+        bytes: text,
+        pos: 0,
+    };
+    for segment in &path.0 {
+        scanner.enter(segment)?;
+    }
+    scanner.peek()?;
+    let before = &text[..scanner.pos];
+    let line = before.iter().filter(|b| **b == b'\n').count() + 1;
+    let column = scanner.pos
+        - before
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1)
+        + 1;
+    Some((line, column))
+}
+
+#[derive(Debug, Clone)]
+pub enum DiagnosticKind {
+    /// JSON 语法错误或字段类型不符.
+    Json {
+        message: String,
+    },
+    Convert {
+        source: ConvertError,
+    },
+}
+
+/// 谱面文件中的一处错误.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub path: JsonPath,
+    /// 行号和列号, 无法确定时为 `None`.
+    pub position: Option<(usize, usize)>,
+    pub kind: DiagnosticKind,
+}
+
+impl Diagnostic {
+    /// 从转换错误中取出路径, 并在 `text` 中找到对应的位置.
+    pub fn from_convert_error(text: &[u8], error: ConvertError) -> Self {
+        let (path, source) = match error {
+            ConvertError::At { path, source } => (path, *source),
+            other => (JsonPath::default(), other),
+        };
+        Self {
+            position: locate(text, &path),
+            path,
+            kind: DiagnosticKind::Convert { source },
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some((line, column)) = self.position {
+            write!(f, " (line {line}, column {column})")?;
+        }
+        match &self.kind {
+            DiagnosticKind::Json { message } => write!(f, ": {message}"),
+            DiagnosticKind::Convert { source } => write!(f, ": {source}"),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// 反序列化 JSON, 出错时给出出错的路径和行列号.
+#[cfg(feature = "deserialize")]
+pub fn from_json<T: DeserializeOwned>(text: &[u8]) -> Result<T, Diagnostic> {
+    let mut deserializer = serde_json::Deserializer::from_slice(text);
+    let (path, error) = match serde_path_to_error::deserialize(&mut deserializer) {
+        Ok(value) => match deserializer.end() {
+            Ok(()) => return Ok(value),
+            Err(error) => (JsonPath::default(), error),
+        },
+        Err(error) => (error.path().into(), error.into_inner()),
+    };
+    Err(json_diagnostic(text, path, error))
+}
+
+/// 从 `text` 解析而来 (可能经过修改) 的 `value` 反序列化, 出错时在 `text` 中查找位置.
+#[cfg(feature = "deserialize")]
+pub fn from_value<T: DeserializeOwned>(
+    text: &[u8],
+    value: serde_json::Value,
+) -> Result<T, Diagnostic> {
+    serde_path_to_error::deserialize(value)
+        .map_err(|error| json_diagnostic(text, error.path().into(), error.into_inner()))
+}
+
+#[cfg(feature = "deserialize")]
+fn json_diagnostic(text: &[u8], path: JsonPath, error: serde_json::Error) -> Diagnostic {
+    // 经过 `flatten` 的字段等处报告的错误没有行列号, 改为查找路径的位置.
+    let position = if error.line() > 0 {
+        Some((error.line(), error.column()))
+    } else {
+        locate(text, &path)
+    };
+    let message = error.to_string();
+    // 行列号单独保存, 不在信息中重复.
+    let suffix = format!(" at line {} column {}", error.line(), error.column());
+    let message = message
+        .strip_suffix(&suffix)
+        .map_or_else(|| message.clone(), str::to_owned);
+    Diagnostic {
+        path,
+        position,
+        kind: DiagnosticKind::Json { message },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_locate() {
+        let text = br#"{
+  "a": 1,
+  "lines": [
+    {"x": [1, {"y": "\"}"}]},
+    {"x": [2, {"y": 3}]}
+  ]
+}"#;
+        let mut path = JsonPath::default();
+        path.key("lines").index(1).key("x").index(1).key("y");
+        assert_eq!(path.to_string(), "lines[1].x[1].y");
+        assert_eq!(locate(text, &path), Some((5, 21)));
+        assert_eq!(locate(text, &JsonPath::default()), Some((1, 1)));
+        path.0[1] = PathSegment::Index(2);
+        assert_eq!(locate(text, &path), None);
+    }
+
+    #[cfg(feature = "deserialize")]
+    #[test]
+    fn test_from_json() {
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Point {
+            time: f32,
+        }
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Doc {
+            points: Vec<Point>,
+        }
+        let error =
+            from_json::<Doc>(b"{\"points\": [{\"time\": 1},\n {\"time\": \"x\"}]}").unwrap_err();
+        assert_eq!(error.path.to_string(), "points[1].time");
+        assert_eq!(error.position, Some((2, 13)));
+        assert!(from_json::<Doc>(b"{\"points\": []").is_err());
+    }
+}
@@ -0,0 +1,440 @@
+//! 基于轨道的谱面, 如 osu!mania 的 `.osu` 文件或简单的 CSV.
+//!
+//! 每个轨道成为一条固定横坐标的竖直线, 由谱师之后在编辑器中调整形状.
+use std::str::FromStr;
+
+use crate::chart::{self, ColorRGBA, EasingId, Spline};
+use snafu::{ensure, OptionExt};
+
+use super::{ConvertResult, EmptyBPMSnafu, InvalidLaneChartSnafu};
+
+/// 以毫秒为单位的速度变化.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimingPoint {
+    pub time: f32,
+    pub bpm: f32,
+}
+
+/// 以毫秒为单位的音符, `length` 为 0 时不是长条.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaneNote {
+    pub time: f32,
+    pub lane: usize,
+    pub length: f32,
+}
+
+/// 轨道数的上限, 每个轨道会成为一条线.
+pub const MAX_LANES: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LaneChart {
+    pub lanes: usize,
+    pub timing_points: Vec<TimingPoint>,
+    pub notes: Vec<LaneNote>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LaneConfig {
+    /// 相邻轨道的间距.
+    pub lane_width: f32,
+    /// 画布速度.
+    pub speed: f32,
+}
+
+impl Default for LaneConfig {
+    fn default() -> Self {
+        Self {
+            lane_width: 120.,
+            speed: 1000.,
+        }
+    }
+}
+
+fn invalid<T>(line: usize, reason: &'static str) -> ConvertResult<T> {
+    InvalidLaneChartSnafu { line, reason }.fail()
+}
+
+fn parse<T: FromStr>(value: &str, line: usize, reason: &'static str) -> ConvertResult<T> {
+    value
+        .parse()
+        .ok()
+        .context(InvalidLaneChartSnafu { line, reason })
+}
+
+impl LaneChart {
+    /// 读取 osu!mania 谱面, 只使用非继承的 timing point.
+    pub fn parse_osu(text: &str) -> ConvertResult<Self> {
+        let mut chart = Self::default();
+        let mut section = "";
+        let mut mode = 0;
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let raw = raw.trim();
+            if raw.is_empty() || raw.starts_with("//") {
+                continue;
+            }
+            if let Some(name) = raw.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+                section = name;
+                continue;
+            }
+            match section {
+                "General" | "Difficulty" => {
+                    let Some((key, value)) = raw.split_once(':') else {
+                        continue;
+                    };
+                    let value = value.trim();
+                    match key.trim() {
+                        "Mode" => mode = parse(value, line, "invalid mode")?,
+                        "CircleSize" => {
+                            let keys: f32 = parse(value, line, "invalid key count")?;
+                            ensure!(
+                                (1.0..=MAX_LANES as f32).contains(&keys.round()),
+                                InvalidLaneChartSnafu {
+                                    line,
+                                    reason: "key count out of range"
+                                }
+                            );
+                            chart.lanes = keys.round() as usize;
+                        }
+                        _ => (),
+                    }
+                }
+                "TimingPoints" => {
+                    let fields: Vec<_> = raw.split(',').map(str::trim).collect();
+                    let [time, beat_length, ..] = fields[..] else {
+                        return invalid(line, "timing point needs at least two fields");
+                    };
+                    // 第 7 个字段为 0 表示继承的 (只改变滑动速度的) timing point.
+                    if fields.get(6).is_some_and(|uninherited| *uninherited == "0") {
+                        continue;
+                    }
+                    let time: f32 = parse(time, line, "invalid time")?;
+                    let beat_length: f32 = parse(beat_length, line, "invalid beat length")?;
+                    ensure!(
+                        beat_length > 0.,
+                        InvalidLaneChartSnafu {
+                            line,
+                            reason: "beat length must be positive"
+                        }
+                    );
+                    chart.timing_points.push(TimingPoint {
+                        time,
+                        bpm: 60_000. / beat_length,
+                    });
+                }
+                "HitObjects" => {
+                    let fields: Vec<_> = raw.split(',').map(str::trim).collect();
+                    let [x, _y, time, kind, ..] = fields[..] else {
+                        return invalid(line, "hit object needs at least four fields");
+                    };
+                    ensure!(
+                        chart.lanes > 0,
+                        InvalidLaneChartSnafu {
+                            line,
+                            reason: "hit object before key count"
+                        }
+                    );
+                    let x: f32 = parse(x, line, "invalid x")?;
+                    let time: f32 = parse(time, line, "invalid time")?;
+                    let kind: u32 = parse(kind, line, "invalid type")?;
+                    let lane =
+                        ((x * chart.lanes as f32 / 512.).floor() as usize).min(chart.lanes - 1);
+                    // 长条的结束时间位于最后一个字段的开头.
+                    let length = if kind & 128 != 0 {
+                        let end = fields
+                            .get(5)
+                            .and_then(|extras| extras.split(':').next())
+                            .context(InvalidLaneChartSnafu {
+                                line,
+                                reason: "hold without end time",
+                            })?;
+                        let end: f32 = parse(end, line, "invalid hold end time")?;
+                        (end - time).max(0.)
+                    } else {
+                        0.
+                    };
+                    chart.notes.push(LaneNote { time, lane, length });
+                }
+                _ => (),
+            }
+        }
+        ensure!(
+            mode == 3,
+            InvalidLaneChartSnafu {
+                line: 0usize,
+                reason: "not an osu!mania beatmap"
+            }
+        );
+        Ok(chart)
+    }
+
+    /// 读取每行为 `时间,轨道,长度` 的 CSV, 时间与长度以毫秒为单位, 长度可以省略.
+    ///
+    /// 以 `#` 开头的行和无法解析的首行 (表头) 会被跳过.
+    pub fn parse_csv(text: &str, bpm: f32) -> ConvertResult<Self> {
+        let mut chart = Self {
+            timing_points: vec![TimingPoint { time: 0., bpm }],
+            ..Default::default()
+        };
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let raw = raw.trim();
+            if raw.is_empty() || raw.starts_with('#') {
+                continue;
+            }
+            let mut fields = raw.split(',').map(str::trim);
+            let (Some(time), Some(lane)) = (fields.next(), fields.next()) else {
+                return invalid(line, "expected time and lane");
+            };
+            let Ok(time) = time.parse::<f32>() else {
+                if index == 0 {
+                    continue;
+                }
+                return invalid(line, "invalid time");
+            };
+            let lane: usize = parse(lane, line, "invalid lane")?;
+            ensure!(
+                lane < MAX_LANES,
+                InvalidLaneChartSnafu {
+                    line,
+                    reason: "lane out of range"
+                }
+            );
+            let length: f32 = match fields.next() {
+                Some(length) if !length.is_empty() => parse(length, line, "invalid length")?,
+                _ => 0.,
+            };
+            chart.lanes = chart.lanes.max(lane + 1);
+            chart.notes.push(LaneNote { time, lane, length });
+        }
+        Ok(chart)
+    }
+
+    /// 毫秒到 beat 的映射, 第一个 timing point 之前沿用它的 BPM.
+    ///
+    /// beat 0 位于第一个 timing point, 使音符落在拍线网格上. 第一项为音乐开始 (0 毫秒) 的位置.
+    fn beat_map(&self) -> ConvertResult<Vec<(f32, f32, f32)>> {
+        let mut points = self.timing_points.clone();
+        points.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let first = points.first().context(EmptyBPMSnafu)?;
+        // (毫秒, beat, bpm)
+        let mut last = (first.time, 0., first.bpm);
+        let changes: Vec<_> = points
+            .iter()
+            .map(|point| {
+                let (last_ms, last_beat, last_bpm) = last;
+                let beat = last_beat + (point.time - last_ms) / 60_000. * last_bpm;
+                last = (point.time, beat, point.bpm);
+                last
+            })
+            .collect();
+        let &(ms, beat, bpm) = changes
+            .iter()
+            .rev()
+            .find(|(ms, ..)| *ms <= 0.)
+            .unwrap_or(&changes[0]);
+        let mut map = vec![(0., beat - ms / 60_000. * bpm, bpm)];
+        for change in changes.into_iter().filter(|(ms, ..)| *ms > 0.) {
+            if change.2 != map.last().unwrap().2 {
+                map.push(change);
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn into_chart(self, config: &LaneConfig) -> ConvertResult<chart::Chart> {
+        let map = self.beat_map()?;
+        let to_beat = |ms: f32| {
+            let &(start_ms, start_beat, bpm) = map
+                .iter()
+                .rev()
+                .find(|(start_ms, ..)| *start_ms <= ms)
+                .unwrap_or(&map[0]);
+            start_beat + (ms - start_ms) / 60_000. * bpm
+        };
+        let lane_count = self
+            .notes
+            .iter()
+            .map(|note| note.lane.saturating_add(1))
+            .fold(self.lanes, usize::max);
+        ensure!(
+            lane_count <= MAX_LANES,
+            InvalidLaneChartSnafu {
+                line: 0usize,
+                reason: "too many lanes"
+            }
+        );
+        let mut lines: Vec<_> = (0..lane_count)
+            .map(|lane| {
+                let x = (lane as f32 - (lane_count as f32 - 1.) / 2.) * config.lane_width;
+                (x, Vec::new())
+            })
+            .collect();
+        let mut last = 0.0f32;
+        for note in &self.notes {
+            let time = to_beat(note.time);
+            let kind = if note.length > 0. {
+                let end = to_beat(note.time + note.length);
+                last = last.max(end);
+                chart::NoteKind::Hold { end }
+            } else {
+                last = last.max(time);
+                chart::NoteKind::Tap
+            };
+            lines[note.lane].1.push(chart::Note::new(time, kind));
+        }
+        let end = last + 1.;
+        let start = map[0].1;
+        let lines = lines
+            .into_iter()
+            .map(|(x, mut notes)| {
+                notes.sort_by(|a: &chart::Note, b| a.time.total_cmp(&b.time));
+                let point = |time: f32| chart::KeyPoint {
+                    time,
+                    value: x,
+                    ease_type: EasingId::Linear,
+                    relevant: chart::LinePointData {
+                        canvas: 0,
+                        color: ColorRGBA::WHITE,
+                        ..Default::default()
+                    },
+                };
+                // 从音乐开始前一拍开始, 使最早的音符也位于线上.
+                let mut line = chart::Line::from_iter([point(start.min(0.) - 1.), point(end)]);
+                line.notes = notes;
+                line.ring_color = Spline::constant(ColorRGBA::WHITE);
+                line
+            })
+            .collect();
+        Ok(chart::Chart {
+            themes: vec![chart::ThemeData {
+                color: chart::ThemeColor {
+                    background: ColorRGBA::BLACK,
+                    note: ColorRGBA::WHITE,
+                    fx: ColorRGBA::WHITE,
+                },
+                is_challenge: false,
+            }],
+            theme_control: Spline::constant(0),
+            lines,
+            canvases: vec![chart::Canvas {
+                x_pos: Spline::constant(0.),
+                // 画布从音乐开始时移动.
+                speed: Spline::from(vec![chart::KeyPoint {
+                    time: start,
+                    value: config.speed,
+                    ease_type: EasingId::Linear,
+                    relevant: (),
+                }]),
+            }],
+            bpm: map
+                .iter()
+                .map(|&(_, beat, bpm)| chart::KeyPoint {
+                    time: beat,
+                    value: bpm,
+                    ease_type: EasingId::Start,
+                    relevant: (),
+                })
+                .collect(),
+            cam_scale: Spline::constant(1.),
+            cam_move: Spline::constant(0.),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::ConvertError;
+
+    #[test]
+    fn test_osu_mania() {
+        let osu = "osu file format v14
+
+[General]
+Mode: 3
+
+[Difficulty]
+CircleSize:4
+
+[TimingPoints]
+1000,500,4,2,0,100,1,0
+2000,-50,4,2,0,100,0,0
+3000,250,4,2,0,100,1,0
+
+[HitObjects]
+64,192,1000,1,0,0:0:0:0:
+448,192,2000,128,0,2500:0:0:0:0:
+192,192,3500,1,0,0:0:0:0:
+";
+        let lanes = LaneChart::parse_osu(osu).unwrap();
+        assert_eq!(lanes.lanes, 4);
+        assert_eq!(lanes.timing_points.len(), 2);
+        let chart = lanes.into_chart(&LaneConfig::default()).unwrap();
+        // 第一个 timing point 在 1000 毫秒, 即 beat 0.
+        let bpm: Vec<_> = chart.bpm.iter().map(|p| [p.time, p.value]).collect();
+        assert_eq!(bpm, [[-2., 120.], [4., 240.]]);
+        assert_eq!(chart.lines.len(), 4);
+        assert_eq!(chart.lines[0].notes[0].time, 0.);
+        assert!(matches!(
+            chart.lines[3].notes[0].kind,
+            chart::NoteKind::Hold { end: 3. }
+        ));
+        assert_eq!(chart.lines[1].notes[0].time, 6.);
+        assert_eq!(chart.lines[0].points.points()[0].value, -180.);
+        let cache = chart::ChartCache::from_chart(&chart);
+        for (note, ms) in [
+            (&chart.lines[0].notes[0], 1000.),
+            (&chart.lines[1].notes[0], 3500.),
+        ] {
+            assert!((cache.remap_beat(note.time) - ms / 1000.).abs() < 1e-4);
+        }
+
+        // 负的 offset: 音乐开始时已经过了半拍.
+        let lanes = LaneChart {
+            lanes: 1,
+            timing_points: vec![TimingPoint {
+                time: -250.,
+                bpm: 120.,
+            }],
+            notes: vec![LaneNote {
+                time: 750.,
+                lane: 0,
+                length: 0.,
+            }],
+        };
+        let chart = lanes.into_chart(&LaneConfig::default()).unwrap();
+        assert_eq!(chart.bpm.points()[0].time, 0.5);
+        assert_eq!(chart.lines[0].notes[0].time, 2.);
+        let cache = chart::ChartCache::from_chart(&chart);
+        assert!((cache.remap_beat(2.) - 0.75).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_lane_csv() {
+        let csv = "time,lane,length\n0,0\n500,2,250\n";
+        let chart = LaneChart::parse_csv(csv, 120.)
+            .unwrap()
+            .into_chart(&LaneConfig::default())
+            .unwrap();
+        assert_eq!(chart.lines.len(), 3);
+        assert!(matches!(
+            chart.lines[2].notes[0].kind,
+            chart::NoteKind::Hold { end: 1.5 }
+        ));
+        assert!(LaneChart::parse_csv("0,0\nx,1\n", 120.).is_err());
+        assert!(matches!(
+            LaneChart::parse_csv("0,100000000\n", 120.),
+            Err(ConvertError::InvalidLaneChart { line: 1, .. })
+        ));
+        let lanes = LaneChart {
+            notes: vec![LaneNote {
+                time: 0.,
+                lane: usize::MAX,
+                length: 0.,
+            }],
+            ..LaneChart::parse_csv("", 120.).unwrap()
+        };
+        assert!(lanes.into_chart(&LaneConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,641 @@
+//! MIDI 与谱面的相互转换.
+//!
+//! 导入时每个 MIDI 音符成为一条线, 导出时每条线成为一个音轨.
+use std::collections::HashMap;
+
+pub use midly;
+use midly::{
+    num::{u15, u24, u28, u4, u7},
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+};
+use snafu::prelude::*;
+
+use super::{
+    ConvertResult, InvalidMidiSnafu, SmpteTimingSnafu, TrackOutOfRangeSnafu, ZeroTicksPerBeatSnafu,
+};
+use crate::{
+    chart::{
+        Canvas, Chart, ChartCache, ColorRGBA, EasingId, KeyPoint, Line, LinePointData, Note,
+        NoteKind, Spline, ThemeColor, ThemeData, Tween,
+    },
+    VIEW_RECT,
+};
+
+/// 没有速度事件时使用的 BPM.
+pub const DEFAULT_BPM: f32 = 120.;
+
+/// 音高到横坐标的映射.
+#[derive(Debug, Clone)]
+pub struct PitchMapping {
+    /// 位于 x = 0 的音高.
+    pub center_key: u8,
+    /// [`VIEW_RECT`] 的宽度对应的音高数.
+    pub keys_across_view: f32,
+    /// 高音在左侧.
+    pub mirror: bool,
+}
+
+impl Default for PitchMapping {
+    fn default() -> Self {
+        Self {
+            // 中央 C.
+            center_key: 60,
+            // 钢琴的键数.
+            keys_across_view: 88.,
+            mirror: false,
+        }
+    }
+}
+
+impl PitchMapping {
+    pub fn x(&self, key: u8) -> f32 {
+        let offset = (key as f32 - self.center_key as f32) / self.keys_across_view
+            * (VIEW_RECT[1][0] - VIEW_RECT[0][0]);
+        if self.mirror {
+            -offset
+        } else {
+            offset
+        }
+    }
+    /// [`Self::x`] 的逆映射, 取最接近的音高.
+    pub fn key(&self, x: f32) -> u8 {
+        let offset = x / (VIEW_RECT[1][0] - VIEW_RECT[0][0]) * self.keys_across_view;
+        let offset = if self.mirror { -offset } else { offset };
+        (self.center_key as f32 + offset).round().clamp(0., 127.) as u8
+    }
+}
+
+/// 力度到颜色的映射, 在两端之间线性插值.
+#[derive(Debug, Clone)]
+pub struct VelocityColor {
+    /// 力度为 0 时的颜色.
+    pub soft: ColorRGBA,
+    /// 力度为 127 时的颜色.
+    pub loud: ColorRGBA,
+}
+
+impl Default for VelocityColor {
+    fn default() -> Self {
+        Self {
+            soft: ColorRGBA::BLACK,
+            loud: ColorRGBA::BLACK,
+        }
+    }
+}
+
+impl VelocityColor {
+    pub fn color(&self, velocity: u8) -> ColorRGBA {
+        ColorRGBA::lerp(self.soft, self.loud, velocity.min(127) as f32 / 127.)
+    }
+}
+
+/// 根据 MIDI 音符长度 (拍) 决定的音符种类.
+#[derive(Debug, Clone)]
+pub struct NoteMapping {
+    /// 是否在每条线的开头放置音符.
+    pub enabled: bool,
+    /// 长度不小于此值的成为长条.
+    pub hold_min: f32,
+    /// 长度小于此值的成为 drag, 其余成为 tap.
+    pub drag_max: f32,
+}
+
+impl Default for NoteMapping {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hold_min: 1.,
+            drag_max: 0.,
+        }
+    }
+}
+
+impl NoteMapping {
+    pub fn kind(&self, start: f32, end: f32) -> NoteKind {
+        let length = end - start;
+        if length >= self.hold_min {
+            NoteKind::Hold { end }
+        } else if length < self.drag_max {
+            NoteKind::Drag
+        } else {
+            NoteKind::Tap
+        }
+    }
+}
+
+/// 线所在的画布.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CanvasAssignment {
+    /// 所有线共用一个画布.
+    #[default]
+    Single,
+    /// 每个被转换的音轨一个画布.
+    PerTrack,
+    /// 每个 MIDI 通道一个画布.
+    PerChannel,
+}
+
+#[derive(Debug, Clone)]
+pub struct MidiImportConfig {
+    /// 被转换为线的音轨下标, `None` 表示所有音轨. 速度事件总是从所有音轨读取.
+    pub tracks: Option<Vec<usize>>,
+    pub pitch: PitchMapping,
+    pub velocity_color: VelocityColor,
+    pub notes: NoteMapping,
+    pub canvas: CanvasAssignment,
+    /// 所有画布的速度.
+    pub canvas_speed: f32,
+}
+
+impl Default for MidiImportConfig {
+    fn default() -> Self {
+        Self {
+            tracks: None,
+            pitch: Default::default(),
+            velocity_color: Default::default(),
+            notes: Default::default(),
+            canvas: Default::default(),
+            canvas_speed: 1000.,
+        }
+    }
+}
+
+/// 单声道的 tempo (每拍微秒数) 转换为 BPM.
+pub fn tempo_to_bpm(tempo: u32) -> f32 {
+    60. * 1e6 / tempo as f32
+}
+
+pub fn tick_to_beat(tick: u32, ticks_per_beat: u32) -> f32 {
+    tick as f32 / ticks_per_beat as f32
+}
+
+fn ticks_per_beat(smf: &Smf) -> ConvertResult<u32> {
+    match smf.header.timing {
+        Timing::Metrical(ticks) => {
+            let ticks = ticks.as_int() as u32;
+            ensure!(ticks != 0, ZeroTicksPerBeatSnafu);
+            Ok(ticks)
+        }
+        Timing::Timecode(..) => SmpteTimingSnafu.fail(),
+    }
+}
+
+/// 所有音轨中的速度事件, 以 beat 为时间.
+pub fn tempo_map(smf: &Smf) -> ConvertResult<Spline<f32>> {
+    let ticks_per_beat = ticks_per_beat(smf)?;
+    let mut tempos: Vec<_> = smf
+        .tracks
+        .iter()
+        .flat_map(|track| {
+            let mut tick = 0;
+            track.iter().filter_map(move |event| {
+                tick += event.delta.as_int();
+                match event.kind {
+                    TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                        Some((tick, tempo_to_bpm(tempo.as_int())))
+                    }
+                    _ => None,
+                }
+            })
+        })
+        .collect();
+    tempos.sort_by_key(|(tick, _)| *tick);
+    // 同一时刻的多个速度事件只保留最后一个.
+    tempos.dedup_by(|later, earlier| {
+        let same = later.0 == earlier.0;
+        if same {
+            earlier.1 = later.1;
+        }
+        same
+    });
+    if tempos.first().is_none_or(|(tick, _)| *tick != 0) {
+        let first = tempos.first().map_or(DEFAULT_BPM, |(_, bpm)| *bpm);
+        tempos.insert(0, (0, first));
+    }
+    Ok(tempos
+        .into_iter()
+        .map(|(tick, bpm)| KeyPoint {
+            time: tick_to_beat(tick, ticks_per_beat),
+            value: bpm,
+            ease_type: EasingId::Start,
+            relevant: (),
+        })
+        .collect())
+}
+
+/// 一个已经结束的 MIDI 音符.
+struct MidiNote {
+    start: f32,
+    end: f32,
+    key: u8,
+    velocity: u8,
+    canvas: usize,
+}
+
+fn track_notes(
+    smf: &Smf,
+    track_idx: usize,
+    canvas: impl Fn(u8) -> usize,
+) -> ConvertResult<Vec<MidiNote>> {
+    let ticks_per_beat = ticks_per_beat(smf)?;
+    let track = smf.tracks.get(track_idx).context(TrackOutOfRangeSnafu {
+        track: track_idx,
+        count: smf.tracks.len(),
+    })?;
+    let mut tick = 0u32;
+    let mut active: HashMap<(u8, u8), (u32, u8)> = HashMap::new();
+    let mut notes = Vec::new();
+    for event in track {
+        tick += event.delta.as_int();
+        let TrackEventKind::Midi { channel, message } = event.kind else {
+            continue;
+        };
+        let channel = channel.as_int();
+        match message {
+            MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                active.insert((channel, key.as_int()), (tick, vel.as_int()));
+            }
+            // 力度为 0 的 NoteOn 等同于 NoteOff.
+            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                let key = key.as_int();
+                if let Some((start, velocity)) = active.remove(&(channel, key)) {
+                    notes.push(MidiNote {
+                        start: tick_to_beat(start, ticks_per_beat),
+                        end: tick_to_beat(tick, ticks_per_beat),
+                        key,
+                        velocity,
+                        canvas: canvas(channel),
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(notes)
+}
+
+fn note_to_line(note: &MidiNote, config: &MidiImportConfig) -> Line {
+    let x = config.pitch.x(note.key);
+    let color = config.velocity_color.color(note.velocity);
+    let point = |time: f32| KeyPoint {
+        time,
+        value: x,
+        ease_type: EasingId::Start,
+        relevant: LinePointData {
+            canvas: note.canvas,
+            color,
+            ..Default::default()
+        },
+    };
+    let mut line = Line::from_iter([point(note.start), point(note.end)]);
+    line.ring_color = Spline::from(vec![KeyPoint {
+        time: 0.,
+        value: color,
+        ease_type: EasingId::Start,
+        relevant: (),
+    }]);
+    if config.notes.enabled {
+        line.notes.push(Note::new(
+            note.start,
+            config.notes.kind(note.start, note.end),
+        ));
+    }
+    line
+}
+
+/// 把已解析的 MIDI 转换为谱面.
+pub fn smf_to_chart(smf: &Smf, config: &MidiImportConfig) -> ConvertResult<Chart> {
+    let tracks = config
+        .tracks
+        .clone()
+        .unwrap_or_else(|| (0..smf.tracks.len()).collect());
+    let mut notes = Vec::new();
+    for (order, track_idx) in tracks.into_iter().enumerate() {
+        notes.extend(track_notes(smf, track_idx, |channel| {
+            match config.canvas {
+                CanvasAssignment::Single => 0,
+                CanvasAssignment::PerTrack => order,
+                CanvasAssignment::PerChannel => channel as usize,
+            }
+        })?);
+    }
+    let canvas_count = notes.iter().map(|note| note.canvas + 1).max().unwrap_or(1);
+    let white = ColorRGBA::WHITE;
+    Ok(Chart {
+        themes: vec![ThemeData {
+            color: ThemeColor {
+                background: white,
+                note: white,
+                fx: white,
+            },
+            is_challenge: false,
+        }],
+        theme_control: Spline::constant(0),
+        lines: notes
+            .iter()
+            .map(|note| note_to_line(note, config))
+            .collect(),
+        canvases: (0..canvas_count)
+            .map(|_| Canvas {
+                x_pos: Spline::constant(0.),
+                speed: Spline::constant_speed(config.canvas_speed),
+            })
+            .collect(),
+        bpm: tempo_map(smf)?,
+        cam_scale: Spline::constant(1.),
+        cam_move: Spline::constant(0.),
+    })
+}
+
+/// 解析 MIDI 文件并转换为谱面.
+pub fn import(bytes: &[u8], config: &MidiImportConfig) -> ConvertResult<Chart> {
+    let smf = Smf::parse(bytes).map_err(|e| {
+        InvalidMidiSnafu {
+            message: e.to_string(),
+        }
+        .build()
+    })?;
+    smf_to_chart(&smf, config)
+}
+
+#[derive(Debug, Clone)]
+pub struct MidiExportConfig {
+    pub ticks_per_beat: u15,
+    /// 由音符击打时线的横坐标决定音高.
+    pub pitch: PitchMapping,
+    pub velocity: u7,
+    pub channel: u4,
+    /// 非长条音符的长度 (拍).
+    pub short_length: f32,
+}
+
+impl Default for MidiExportConfig {
+    fn default() -> Self {
+        Self {
+            ticks_per_beat: u15::new(480),
+            pitch: Default::default(),
+            velocity: u7::new(100),
+            channel: u4::new(0),
+            short_length: 0.25,
+        }
+    }
+}
+
+pub fn beat_to_tick(beat: f32, ticks_per_beat: u32) -> u32 {
+    (beat.max(0.) * ticks_per_beat as f32).round() as u32
+}
+
+pub fn bpm_to_tempo(bpm: f32) -> u24 {
+    let tempo = (60. * 1e6 / bpm).round();
+    u24::new(tempo.clamp(1., u24::max_value().as_int() as f32) as u32)
+}
+
+/// 把按绝对 tick 排列的事件转换为音轨.
+fn to_track<'a>(mut events: Vec<(u32, TrackEventKind<'a>)>) -> Vec<TrackEvent<'a>> {
+    // 同一时刻先结束再开始, 避免相邻的同音高音符互相截断.
+    let order = |kind: &TrackEventKind| match kind {
+        TrackEventKind::Midi {
+            message: MidiMessage::NoteOff { .. },
+            ..
+        } => 0,
+        TrackEventKind::Meta(_) => 1,
+        _ => 2,
+    };
+    events.sort_by_key(|(tick, kind)| (*tick, order(kind)));
+    let mut last = 0;
+    let mut track: Vec<_> = events
+        .into_iter()
+        .map(|(tick, kind)| {
+            let delta = tick - last;
+            last = tick;
+            TrackEvent {
+                delta: u28::new(delta),
+                kind,
+            }
+        })
+        .collect();
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+    track
+}
+
+/// 把谱面的音符写入标准 MIDI 文件.
+///
+/// 第一个音轨是速度表, 之后每条线一个音轨. BPM 的缓动会被忽略, 只在关键点处改变速度.
+pub fn export(chart: &Chart, config: &MidiExportConfig) -> ConvertResult<Vec<u8>> {
+    let ticks_per_beat = config.ticks_per_beat.as_int() as u32;
+    ensure!(ticks_per_beat != 0, ZeroTicksPerBeatSnafu);
+    let cache = ChartCache::from_chart(chart);
+    let with_cache = chart.with_cache(&cache);
+    let names: Vec<_> = (0..chart.lines.len())
+        .map(|i| format!("Line {i}").into_bytes())
+        .collect();
+
+    let mut smf = Smf::new(Header::new(
+        Format::Parallel,
+        Timing::Metrical(config.ticks_per_beat),
+    ));
+    smf.tracks.push(to_track(
+        chart
+            .bpm
+            .iter()
+            .map(|point| {
+                (
+                    beat_to_tick(point.time, ticks_per_beat),
+                    TrackEventKind::Meta(MetaMessage::Tempo(bpm_to_tempo(point.value))),
+                )
+            })
+            .collect(),
+    ));
+    for (line_idx, line) in chart.lines.iter().enumerate() {
+        let mut events = vec![(
+            0,
+            TrackEventKind::Meta(MetaMessage::TrackName(&names[line_idx])),
+        )];
+        for note in &line.notes {
+            // 音符常常恰好位于线的端点上; 只有一个点的线取这个点的横坐标.
+            let x = with_cache
+                .line_pos_at_clamped(line_idx, note.time, note.time)
+                .map(|[x, _]| x)
+                .or_else(|| line.points.first().map(|point| point.value))
+                .unwrap_or(0.);
+            let key = u7::new(config.pitch.key(x));
+            let end = match note.kind {
+                NoteKind::Hold { end } => end,
+                _ => note.time + config.short_length,
+            };
+            let start = beat_to_tick(note.time, ticks_per_beat);
+            let end = beat_to_tick(end, ticks_per_beat).max(start + 1);
+            let midi = |message| TrackEventKind::Midi {
+                channel: config.channel,
+                message,
+            };
+            events.push((
+                start,
+                midi(MidiMessage::NoteOn {
+                    key,
+                    vel: config.velocity,
+                }),
+            ));
+            events.push((
+                end,
+                midi(MidiMessage::NoteOff {
+                    key,
+                    vel: u7::new(0),
+                }),
+            ));
+        }
+        smf.tracks.push(to_track(events));
+    }
+    let mut bytes = Vec::new();
+    smf.write(&mut bytes).map_err(|e| {
+        InvalidMidiSnafu {
+            message: e.to_string(),
+        }
+        .build()
+    })?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::ConvertError;
+
+    fn event(delta: u32, kind: TrackEventKind<'static>) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: u28::new(delta),
+            kind,
+        }
+    }
+
+    fn note(delta: u32, key: u8, vel: u8) -> TrackEvent<'static> {
+        let message = if vel > 0 {
+            MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(vel),
+            }
+        } else {
+            MidiMessage::NoteOff {
+                key: u7::new(key),
+                vel: u7::new(0),
+            }
+        };
+        event(
+            delta,
+            TrackEventKind::Midi {
+                channel: u4::new(0),
+                message,
+            },
+        )
+    }
+
+    #[test]
+    fn test_midi_import() {
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(480)),
+        ));
+        smf.tracks.push(vec![
+            event(0, TrackEventKind::Meta(MetaMessage::Tempo(500_000.into()))),
+            note(0, 60, 100),
+            note(240, 60, 0),
+            note(240, 72, 127),
+            event(0, TrackEventKind::Meta(MetaMessage::Tempo(250_000.into()))),
+            note(960, 72, 0),
+        ]);
+        let mut bytes = Vec::new();
+        smf.write(&mut bytes).unwrap();
+        let chart = import(&bytes, &MidiImportConfig::default()).unwrap();
+
+        let bpm: Vec<_> = chart.bpm.iter().map(|p| [p.time, p.value]).collect();
+        assert_eq!(bpm, [[0., 120.], [1., 240.]]);
+        assert_eq!(chart.lines.len(), 2);
+        assert_eq!(chart.lines[0].notes[0].kind, NoteKind::Tap);
+        assert!((chart.lines[1].points.points()[0].value - 12. / 88. * 900.).abs() < 1e-3);
+        assert!(matches!(
+            chart.lines[1].notes[0].kind,
+            NoteKind::Hold { end: 3. }
+        ));
+
+        let config = MidiImportConfig {
+            tracks: Some(vec![1]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            import(&bytes, &config),
+            Err(ConvertError::TrackOutOfRange { track: 1, count: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_midi_export_round_trip() {
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(96)),
+        ));
+        smf.tracks.push(vec![
+            event(0, TrackEventKind::Meta(MetaMessage::Tempo(600_000.into()))),
+            note(0, 48, 100),
+            note(48, 48, 0),
+            note(48, 67, 100),
+            note(192, 67, 0),
+        ]);
+        let mut bytes = Vec::new();
+        smf.write(&mut bytes).unwrap();
+        let chart = import(&bytes, &MidiImportConfig::default()).unwrap();
+
+        let exported = export(&chart, &MidiExportConfig::default()).unwrap();
+        let smf = Smf::parse(&exported).unwrap();
+        assert_eq!(smf.tracks.len(), 1 + chart.lines.len());
+        let round_trip = smf_to_chart(&smf, &MidiImportConfig::default()).unwrap();
+        let bpm: Vec<_> = round_trip.bpm.iter().map(|p| [p.time, p.value]).collect();
+        assert_eq!(bpm, [[0., 100.]]);
+        let summary = |chart: &Chart| -> Vec<_> {
+            chart
+                .lines
+                .iter()
+                .map(|line| {
+                    let point = &line.points.points()[0];
+                    (point.time, point.value, line.notes[0].kind.clone())
+                })
+                .collect()
+        };
+        let original = summary(&chart);
+        let round_trip = summary(&round_trip);
+        assert_eq!(round_trip.len(), 2);
+        for (a, b) in original.iter().zip(&round_trip) {
+            assert!((a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-3);
+            assert_eq!(a.2, b.2);
+        }
+        assert!(matches!(round_trip[1].2, NoteKind::Hold { end: 3. }));
+    }
+
+    #[test]
+    fn test_midi_export_short_lines() {
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(480)),
+        ));
+        smf.tracks.push(vec![note(0, 60, 100), note(5, 60, 0)]);
+        let mut bytes = Vec::new();
+        smf.write(&mut bytes).unwrap();
+        let mut chart = import(&bytes, &MidiImportConfig::default()).unwrap();
+        assert!(chart.lines[0].points.end_time().unwrap() < 0.02);
+        let mut single = chart.lines[0].clone();
+        single.points.points.truncate(1);
+        chart.lines.push(single);
+
+        let exported = export(&chart, &MidiExportConfig::default()).unwrap();
+        let round_trip = import(&exported, &MidiImportConfig::default()).unwrap();
+        let keys: Vec<_> = round_trip
+            .lines
+            .iter()
+            .map(|line| line.points.points()[0].value)
+            .collect();
+        assert_eq!(keys.len(), 2);
+        assert!((keys[0] - keys[1]).abs() < 1e-3);
+    }
+}
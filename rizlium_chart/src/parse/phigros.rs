@@ -0,0 +1,458 @@
+//! Phigros 官方谱面格式 (formatVersion 1 与 3).
+//!
+//! 每条判定线成为一个画布, 判定线上每个不同的 `positionX` 成为一条竖直的线.
+//! 旋转, 透明度和纵向移动无法表示, 会作为 [`ConvertWarning`] 返回.
+use std::collections::BTreeMap;
+
+use crate::chart::{self, ColorRGBA, EasingId, Spline};
+use crate::VIEW_RECT;
+#[cfg(feature = "deserialize")]
+use serde::Deserialize;
+#[cfg(feature = "serialize")]
+use serde::Serialize;
+use snafu::ensure;
+use tracing::warn;
+
+use super::{ConvertError, ConvertResult, ConvertWarning, EmptyBPMSnafu};
+
+/// `positionX` 的单位相对画面宽度的比例.
+const POSITION_X_UNIT: f32 = 0.05625;
+/// 速度为 1 时每秒移动的画面高度.
+const SPEED_UNIT: f32 = 0.6;
+
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(
+    any(feature = "serialize", feature = "deserialize"),
+    serde(rename_all = "camelCase")
+)]
+pub struct Note {
+    /// 1: tap, 2: drag, 3: hold, 4: flick.
+    #[cfg_attr(
+        any(feature = "serialize", feature = "deserialize"),
+        serde(rename = "type")
+    )]
+    pub note_type: u8,
+
+    pub time: f32,
+
+    pub position_x: f32,
+
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(default))]
+    pub hold_time: f32,
+
+    pub speed: f32,
+
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(default))]
+    pub floor_position: f32,
+}
+
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(
+    any(feature = "serialize", feature = "deserialize"),
+    serde(rename_all = "camelCase")
+)]
+pub struct SpeedEvent {
+    pub start_time: f32,
+
+    pub end_time: f32,
+
+    pub value: f32,
+}
+
+/// 移动, 旋转和透明度事件. 只有移动事件使用 `start2` 与 `end2`.
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(
+    any(feature = "serialize", feature = "deserialize"),
+    serde(rename_all = "camelCase")
+)]
+pub struct ValueEvent {
+    pub start_time: f32,
+
+    pub end_time: f32,
+
+    pub start: f32,
+
+    pub end: f32,
+
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(default))]
+    pub start2: f32,
+
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(default))]
+    pub end2: f32,
+}
+
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(
+    any(feature = "serialize", feature = "deserialize"),
+    serde(rename_all = "camelCase")
+)]
+pub struct JudgeLine {
+    pub bpm: f32,
+
+    pub notes_above: Vec<Note>,
+
+    pub notes_below: Vec<Note>,
+
+    pub speed_events: Vec<SpeedEvent>,
+
+    pub judge_line_move_events: Vec<ValueEvent>,
+
+    pub judge_line_rotate_events: Vec<ValueEvent>,
+
+    pub judge_line_disappear_events: Vec<ValueEvent>,
+}
+
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(
+    any(feature = "serialize", feature = "deserialize"),
+    serde(rename_all = "camelCase")
+)]
+pub struct PhigrosChart {
+    pub format_version: u32,
+
+    pub offset: f32,
+
+    pub judge_line_list: Vec<JudgeLine>,
+}
+
+/// 把判定线自己的时间 (1/32 拍) 换算为谱面的 beat.
+struct TimeScale {
+    line_bpm: f32,
+    chart_bpm: f32,
+}
+
+impl TimeScale {
+    fn beat(&self, time: f32) -> f32 {
+        time / 32. * self.chart_bpm / self.line_bpm
+    }
+}
+
+/// 画面横坐标 (0 到 1) 转换为以画面中心为 0 的坐标.
+fn screen_x(x: f32) -> f32 {
+    (x - 0.5) * (VIEW_RECT[1][0] - VIEW_RECT[0][0])
+}
+
+impl ValueEvent {
+    /// 移动事件的 (x, y), 均为 0 到 1 的画面坐标.
+    fn position(&self, format_version: u32) -> [[f32; 2]; 2] {
+        if format_version == 1 {
+            // 旧格式中 x 与 y 被压缩在同一个数里.
+            let unpack = |packed: f32| [(packed / 1000.).floor() / 880., packed % 1000. / 520.];
+            [unpack(self.start), unpack(self.end)]
+        } else {
+            [[self.start, self.start2], [self.end, self.end2]]
+        }
+    }
+}
+
+struct Converter {
+    format_version: u32,
+    chart_bpm: f32,
+    warnings: Vec<ConvertWarning>,
+}
+
+impl Converter {
+    fn warn(&mut self, line_idx: Option<usize>, feature: &'static str, count: usize) {
+        if count > 0 {
+            self.warnings.push(ConvertWarning {
+                line_idx,
+                feature,
+                count,
+            });
+        }
+    }
+
+    fn canvas(&mut self, line_idx: usize, line: &JudgeLine, scale: &TimeScale) -> chart::Canvas {
+        let mut speed: Vec<_> = line
+            .speed_events
+            .iter()
+            .map(|event| chart::KeyPoint {
+                time: scale.beat(event.start_time),
+                value: event.value * SPEED_UNIT * (VIEW_RECT[1][1] - VIEW_RECT[0][1]),
+                // 非 `Start` 的速度在缓存中视为分段常数.
+                ease_type: EasingId::Linear,
+                relevant: (),
+            })
+            .collect();
+
+        let moves: Vec<_> = line
+            .judge_line_move_events
+            .iter()
+            .map(|event| (event, event.position(self.format_version)))
+            .collect();
+        let mut x_pos = Vec::with_capacity(moves.len());
+        for (i, (event, [start, end])) in moves.iter().enumerate() {
+            x_pos.push(chart::KeyPoint {
+                time: scale.beat(event.start_time),
+                value: screen_x(start[0]),
+                ease_type: EasingId::Linear,
+                relevant: (),
+            });
+            let continuous = moves
+                .get(i + 1)
+                .is_some_and(|(_, [next, _])| next[0] == end[0]);
+            if !continuous {
+                x_pos.push(chart::KeyPoint {
+                    time: scale.beat(event.end_time),
+                    value: screen_x(end[0]),
+                    ease_type: EasingId::Start,
+                    relevant: (),
+                });
+            }
+        }
+        let first_y = moves.first().map(|(_, [start, _])| start[1]);
+        let vertical = moves
+            .iter()
+            .filter(|(_, [start, end])| Some(start[1]) != first_y || Some(end[1]) != first_y)
+            .count();
+        self.warn(
+            Some(line_idx),
+            "vertical line movement is ignored",
+            vertical,
+        );
+
+        let rotated = line
+            .judge_line_rotate_events
+            .iter()
+            .filter(|event| event.start % 360. != 0. || event.end % 360. != 0.)
+            .count();
+        self.warn(Some(line_idx), "line rotation is ignored", rotated);
+        let faded = line
+            .judge_line_disappear_events
+            .iter()
+            .filter(|event| event.start != 1. || event.end != 1.)
+            .count();
+        self.warn(Some(line_idx), "line alpha is ignored", faded);
+
+        // 缓存要求画布至少有一个速度关键点.
+        if speed.is_empty() {
+            speed.push(chart::KeyPoint {
+                time: 0.,
+                value: SPEED_UNIT * (VIEW_RECT[1][1] - VIEW_RECT[0][1]),
+                ease_type: EasingId::Linear,
+                relevant: (),
+            });
+        }
+        if x_pos.is_empty() {
+            x_pos.push(chart::KeyPoint::default());
+        }
+        chart::Canvas {
+            x_pos: x_pos.into_iter().collect(),
+            speed: speed.into_iter().collect(),
+        }
+    }
+
+    fn note(
+        &mut self,
+        line_idx: usize,
+        note: &Note,
+        scale: &TimeScale,
+    ) -> ConvertResult<chart::Note> {
+        let time = scale.beat(note.time);
+        let kind = match note.note_type {
+            1 => chart::NoteKind::Tap,
+            2 => chart::NoteKind::Drag,
+            3 => chart::NoteKind::Hold {
+                end: scale.beat(note.time + note.hold_time),
+            },
+            4 => {
+                self.warn(Some(line_idx), "flick is imported as drag", 1);
+                chart::NoteKind::Drag
+            }
+            otherwise => {
+                return Err(ConvertError::UnknownNoteKind {
+                    raw_kind: otherwise as usize,
+                })
+            }
+        };
+        // 长条的 speed 表示按住时的下落速度, 不需要处理.
+        if note.note_type != 3 && note.speed != 1. {
+            self.warn(Some(line_idx), "note speed is ignored", 1);
+        }
+        Ok(chart::Note::new(time, kind))
+    }
+
+    /// 每个 `positionX` 一条竖直的线, 从第一个音符前一拍到最后一个音符结束.
+    fn lines(
+        &mut self,
+        line_idx: usize,
+        line: &JudgeLine,
+        scale: &TimeScale,
+    ) -> ConvertResult<Vec<chart::Line>> {
+        self.warn(
+            Some(line_idx),
+            "notes below the line are placed above it",
+            line.notes_below.len(),
+        );
+        let mut lanes: BTreeMap<i64, Vec<chart::Note>> = BTreeMap::new();
+        for note in line.notes_above.iter().chain(&line.notes_below) {
+            let lane = (note.position_x * 1000.).round() as i64;
+            let note = self.note(line_idx, note, scale)?;
+            lanes.entry(lane).or_default().push(note);
+        }
+        Ok(lanes
+            .into_iter()
+            .map(|(lane, mut notes)| {
+                notes.sort_by(|a, b| a.time.total_cmp(&b.time));
+                let x = lane as f32 / 1000. * POSITION_X_UNIT * (VIEW_RECT[1][0] - VIEW_RECT[0][0]);
+                let start = notes.first().map_or(0., |note| note.time) - 1.;
+                let end = notes
+                    .iter()
+                    .map(|note| match note.kind {
+                        chart::NoteKind::Hold { end } => end,
+                        _ => note.time,
+                    })
+                    .fold(start + 1., f32::max)
+                    + 1.;
+                let point = |time: f32| chart::KeyPoint {
+                    time,
+                    value: x,
+                    ease_type: EasingId::Linear,
+                    relevant: chart::LinePointData {
+                        canvas: line_idx,
+                        color: ColorRGBA::WHITE,
+                        ..Default::default()
+                    },
+                };
+                let mut line = chart::Line::from_iter([point(start), point(end)]);
+                line.notes = notes;
+                line.ring_color = Spline::constant(ColorRGBA::WHITE);
+                line
+            })
+            .collect())
+    }
+}
+
+impl PhigrosChart {
+    /// 有损地转换为谱面, 同时返回被忽略或近似处理的内容.
+    ///
+    /// 谱面使用第一条判定线的 BPM, 其余判定线的时间会按各自的 BPM 换算.
+    pub fn convert(self) -> ConvertResult<(chart::Chart, Vec<ConvertWarning>)> {
+        let chart_bpm = match self.judge_line_list.first() {
+            Some(line) if line.bpm > 0. => line.bpm,
+            _ => return EmptyBPMSnafu.fail(),
+        };
+        let mut converter = Converter {
+            format_version: self.format_version,
+            chart_bpm,
+            warnings: Vec::new(),
+        };
+        if self.offset != 0. {
+            converter.warn(None, "music offset is ignored", 1);
+        }
+        let mut canvases = Vec::with_capacity(self.judge_line_list.len());
+        let mut lines = Vec::new();
+        for (line_idx, line) in self.judge_line_list.iter().enumerate() {
+            ensure!(line.bpm > 0., EmptyBPMSnafu);
+            let scale = TimeScale {
+                line_bpm: line.bpm,
+                chart_bpm: converter.chart_bpm,
+            };
+            canvases.push(converter.canvas(line_idx, line, &scale));
+            lines.extend(converter.lines(line_idx, line, &scale)?);
+        }
+        let mut warnings = converter.warnings;
+        // 合并同一条线上的相同警告.
+        warnings.sort_by_key(|w| (w.line_idx, w.feature));
+        warnings.dedup_by(|later, earlier| {
+            let same = later.line_idx == earlier.line_idx && later.feature == earlier.feature;
+            if same {
+                earlier.count += later.count;
+            }
+            same
+        });
+        let chart = chart::Chart {
+            themes: vec![chart::ThemeData {
+                color: chart::ThemeColor {
+                    background: ColorRGBA::BLACK,
+                    note: ColorRGBA::WHITE,
+                    fx: ColorRGBA::WHITE,
+                },
+                is_challenge: false,
+            }],
+            theme_control: Spline::constant(0),
+            lines,
+            canvases,
+            bpm: Spline::constant(chart_bpm),
+            cam_scale: Spline::constant(1.),
+            cam_move: Spline::constant(0.),
+        };
+        Ok((chart, warnings))
+    }
+}
+
+impl TryInto<chart::Chart> for PhigrosChart {
+    type Error = ConvertError;
+
+    /// 警告只会被记录到日志中, 需要警告时使用 [`PhigrosChart::convert`].
+    fn try_into(self) -> ConvertResult<chart::Chart> {
+        let (chart, warnings) = self.convert()?;
+        for warning in warnings {
+            warn!("phigros: {warning}");
+        }
+        Ok(chart)
+    }
+}
+
+#[cfg(all(test, feature = "deserialize"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_phigros_convert() {
+        let json = r#"{
+            "formatVersion": 3,
+            "offset": 0.0,
+            "judgeLineList": [{
+                "bpm": 120.0,
+                "notesAbove": [
+                    {"type": 1, "time": 32, "positionX": 0.0, "holdTime": 0, "speed": 1.0, "floorPosition": 0.6},
+                    {"type": 3, "time": 64, "positionX": 2.0, "holdTime": 32, "speed": 2.0, "floorPosition": 1.2},
+                    {"type": 4, "time": 96, "positionX": 0.0, "holdTime": 0, "speed": 1.0, "floorPosition": 1.8}
+                ],
+                "notesBelow": [
+                    {"type": 2, "time": 128, "positionX": 2.0, "holdTime": 0, "speed": 1.0, "floorPosition": 2.4}
+                ],
+                "speedEvents": [{"startTime": 0, "endTime": 999999, "value": 1.0}],
+                "judgeLineMoveEvents": [
+                    {"startTime": 0, "endTime": 64, "start": 0.5, "end": 0.75, "start2": 0.2, "end2": 0.2},
+                    {"startTime": 64, "endTime": 999999, "start": 0.75, "end": 0.75, "start2": 0.2, "end2": 0.2}
+                ],
+                "judgeLineRotateEvents": [
+                    {"startTime": 0, "endTime": 999999, "start": 0.0, "end": 15.0}
+                ],
+                "judgeLineDisappearEvents": [
+                    {"startTime": 0, "endTime": 999999, "start": 1.0, "end": 1.0}
+                ]
+            }]
+        }"#;
+        let chart: PhigrosChart = serde_json::from_str(json).unwrap();
+        let (chart, warnings) = chart.convert().unwrap();
+        assert_eq!(chart.canvases.len(), 1);
+        assert_eq!(chart.lines.len(), 2);
+        let times: Vec<_> = chart.lines[0].notes.iter().map(|n| n.time).collect();
+        assert_eq!(times, [1., 3.]);
+        assert!(matches!(
+            chart.lines[1].notes[0].kind,
+            chart::NoteKind::Hold { end: 3. }
+        ));
+        assert_eq!(chart.lines[1].points.points()[0].value, 2. * 0.05625 * 900.);
+        let x: Vec<_> = chart.canvases[0].x_pos.iter().map(|p| p.value).collect();
+        assert_eq!(x, [0., 225., 225.]);
+
+        let features: Vec<_> = warnings.iter().map(|w| (w.feature, w.count)).collect();
+        assert_eq!(
+            features,
+            [
+                ("flick is imported as drag", 1),
+                ("line rotation is ignored", 1),
+                ("notes below the line are placed above it", 1),
+            ]
+        );
+    }
+}
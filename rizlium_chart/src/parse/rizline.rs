@@ -1,19 +1,64 @@
 use crate::VIEW_RECT;
 
 use crate::chart::{self, Spline};
-use crate::parse::EmptyBPMSnafu;
 use chart::LinePointData;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 #[cfg(feature = "deserialize")]
 use serde::Deserialize;
 #[cfg(feature = "serialize")]
 use serde::Serialize;
-use snafu::{ensure, OptionExt};
+use snafu::ensure;
 use tracing::info;
 
-use super::{ConvertError, ConvertResult, HoldNoEndSnafu};
+#[cfg(feature = "deserialize")]
+use super::diagnostic::Diagnostic;
+use super::{diagnostic::JsonPath, ConvertError, ConvertResult, EmptyBPMSnafu};
+
+#[cfg(feature = "deserialize")]
+mod versions;
+#[cfg(feature = "deserialize")]
+pub use versions::*;
+
+/// 较新的游戏版本可能加入的, 这里没有声明的字段.
+pub type Extra = serde_json::Map<String, serde_json::Value>;
+
+/// 转换时收集到的错误, 每个错误都带有在原文件中的路径.
+#[derive(Default)]
+struct Errors {
+    path: JsonPath,
+    errors: Vec<ConvertError>,
+}
+
+impl Errors {
+    fn push(&mut self, field: &'static str, source: ConvertError) {
+        let mut path = self.path.clone();
+        path.key(field);
+        self.errors.push(ConvertError::At {
+            path,
+            source: Box::new(source),
+        });
+    }
+    /// 在 `field[index]` 之下执行 `f`.
+    fn item<R>(&mut self, field: &'static str, index: usize, f: impl FnOnce(&mut Self) -> R) -> R {
+        let len = self.path.0.len();
+        self.path.key(field).index(index);
+        let result = f(self);
+        self.path.0.truncate(len);
+        result
+    }
+    /// 无法识别的缓动记录错误后视为线性.
+    fn ease(&mut self, raw_kind: u8) -> chart::EasingId {
+        raw_kind.try_into().unwrap_or_else(|_| {
+            self.push("easeType", ConvertError::UnknownEaseKind { raw_kind });
+            chart::EasingId::Linear
+        })
+    }
+}
 
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -38,6 +83,7 @@ impl Theme {
 
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -53,6 +99,7 @@ pub struct ChallengeTime {
 }
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -69,33 +116,44 @@ pub struct Note {
     pub floor_position: f32,
 
     pub other_informations: Vec<f32>,
+
+    /// 未声明的字段, 序列化时原样写回.
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(flatten))]
+    pub extra: Extra,
 }
 impl Note {
-    fn convert(self, line_idx: usize, note_idx: usize) -> ConvertResult<chart::Note> {
-        Ok(chart::Note::new(
-            self.time,
-            match self.note_type {
-                0 => chart::NoteKind::Tap,
-                1 => chart::NoteKind::Drag,
-                2 => chart::NoteKind::Hold {
-                    end: *self
-                        .other_informations
-                        .first()
-                        .with_context(|| HoldNoEndSnafu { line_idx, note_idx })?,
-                },
-                otherwise => {
-                    return Err(ConvertError::UnknownNoteKind {
-                        raw_kind: otherwise as usize,
-                    })
+    fn convert(self, line_idx: usize, note_idx: usize, errors: &mut Errors) -> chart::Note {
+        let kind = match self.note_type {
+            0 => chart::NoteKind::Tap,
+            1 => chart::NoteKind::Drag,
+            2 => match self.other_informations.first() {
+                Some(end) => chart::NoteKind::Hold { end: *end },
+                None => {
+                    errors.push(
+                        "otherInformations",
+                        ConvertError::HoldNoEnd { line_idx, note_idx },
+                    );
+                    chart::NoteKind::Tap
                 }
             },
-        ))
+            otherwise => {
+                errors.push(
+                    "type",
+                    ConvertError::UnknownNoteKind {
+                        raw_kind: otherwise as usize,
+                    },
+                );
+                chart::NoteKind::Tap
+            }
+        };
+        chart::Note::new(self.time, kind)
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -123,6 +181,7 @@ impl From<ColorRGBA> for chart::ColorRGBA {
 
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -139,30 +198,30 @@ pub struct LinePoint {
     pub canvas_index: usize,
 
     pub floor_position: f32,
+
+    /// 未声明的字段, 序列化时原样写回.
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(flatten))]
+    pub extra: Extra,
 }
 
 impl LinePoint {
-    fn convert(self) -> ConvertResult<chart::KeyPoint<f32, chart::LinePointData>> {
+    fn convert(self, errors: &mut Errors) -> chart::KeyPoint<f32, chart::LinePointData> {
         let color: chart::ColorRGBA = self.color.into();
-        let point = chart::KeyPoint {
+        chart::KeyPoint {
             time: self.time,
             value: self.x_position,
-            ease_type: self
-                .ease_type
-                .try_into()
-                .or(Err(ConvertError::UnknownEaseKind {
-                    raw_kind: self.ease_type,
-                }))?,
+            ease_type: errors.ease(self.ease_type),
             relevant: LinePointData {
                 canvas: self.canvas_index,
                 color,
+                ..Default::default()
             },
-        };
-        Ok(point)
+        }
     }
 }
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -186,6 +245,7 @@ impl From<ColorKeyPoint> for chart::KeyPoint<chart::ColorRGBA> {
 }
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -198,9 +258,13 @@ pub struct Line {
     pub judge_ring_color: Vec<ColorKeyPoint>,
 
     pub line_color: Vec<ColorKeyPoint>,
+
+    /// 未声明的字段, 序列化时原样写回.
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(flatten))]
+    pub extra: Extra,
 }
 impl Line {
-    fn convert(self, line_index: usize) -> ConvertResult<chart::Line> {
+    fn convert(self, line_index: usize, errors: &mut Errors) -> chart::Line {
         let line_color: Spline<_> = self
             .line_color
             .windows(2)
@@ -229,13 +293,11 @@ impl Line {
                 relevant: (),
             }))
             .collect();
-        let points = self
+        let points: Spline<_, _> = self
             .line_points
             .into_iter()
-            .map(|p| p.convert())
-            .collect::<ConvertResult<Vec<_>>>()?;
-        let points: Spline<_, _> = points
-            .into_iter()
+            .enumerate()
+            .map(|(idx, p)| errors.item("linePoints", idx, |errors| p.convert(errors)))
             .map(|mut x| {
                 x.value = scale_x(x.value);
                 x
@@ -245,9 +307,9 @@ impl Line {
             .notes
             .into_iter()
             .enumerate()
-            .map(|(idx, n)| n.convert(line_index, idx))
-            .collect::<Result<Vec<_>, _>>()?;
-        Ok(chart::Line {
+            .map(|(idx, n)| errors.item("notes", idx, |errors| n.convert(line_index, idx, errors)))
+            .collect();
+        chart::Line {
             points,
             notes,
             ring_color: self
@@ -279,7 +341,8 @@ impl Line {
                 }))
                 .collect(),
             line_color,
-        })
+            speed: Spline::EMPTY,
+        }
     }
 }
 
@@ -292,6 +355,7 @@ fn scale_y(y: f32) -> f32 {
 
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -302,42 +366,39 @@ pub struct CanvasMove {
     pub x_position_key_points: Vec<KeyPoint>,
 
     pub speed_key_points: Vec<KeyPoint>,
+
+    /// 未声明的字段, 序列化时原样写回.
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(flatten))]
+    pub extra: Extra,
 }
 
 impl CanvasMove {
-    fn convert(self) -> ConvertResult<chart::Canvas> {
-        Ok(chart::Canvas {
-            x_pos: self
-                .x_position_key_points
-                .into_iter()
-                .map(TryInto::try_into)
-                .map(|p: Result<chart::KeyPoint<f32>, _>| {
-                    let mut p = p?;
+    fn convert(self, errors: &mut Errors) -> chart::Canvas {
+        chart::Canvas {
+            x_pos: convert_key_points(self.x_position_key_points, "xPositionKeyPoints", errors)
+                .map(|mut p| {
                     p.value = scale_x(p.value);
-                    Ok(p)
+                    p
                 })
-                .collect::<Result<_, _>>()?,
+                .collect(),
 
-            speed: self
-                .speed_key_points
-                .into_iter()
-                .map(TryInto::try_into)
-                .map(|p: Result<chart::KeyPoint<f32>, _>| {
-                    let mut p = p?;
+            speed: convert_key_points(self.speed_key_points, "speedKeyPoints", errors)
+                .map(|mut p| {
                     p.value = scale_y(p.value);
                     // linear here actually means constant start value
                     if p.ease_type == chart::EasingId::Linear {
                         p.ease_type = chart::EasingId::QuadOut;
                     }
-                    Ok(p)
+                    p
                 })
-                .collect::<Result<_, _>>()?,
-        })
+                .collect(),
+        }
     }
 }
 
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -352,6 +413,28 @@ pub struct KeyPoint {
     pub floor_position: f32,
 }
 
+impl KeyPoint {
+    fn convert(self, errors: &mut Errors) -> chart::KeyPoint<f32> {
+        chart::KeyPoint {
+            time: self.time,
+            value: self.value,
+            ease_type: errors.ease(self.ease_type),
+            relevant: (),
+        }
+    }
+}
+
+fn convert_key_points<'a>(
+    points: Vec<KeyPoint>,
+    field: &'static str,
+    errors: &'a mut Errors,
+) -> impl Iterator<Item = chart::KeyPoint<f32>> + 'a {
+    points
+        .into_iter()
+        .enumerate()
+        .map(move |(idx, p)| errors.item(field, idx, |errors| p.convert(errors)))
+}
+
 impl TryInto<chart::KeyPoint<f32>> for KeyPoint {
     type Error = ConvertError;
     fn try_into(self) -> ConvertResult<chart::KeyPoint<f32>> {
@@ -372,6 +455,7 @@ impl TryInto<chart::KeyPoint<f32>> for KeyPoint {
 // todo
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -380,10 +464,15 @@ pub struct CameraMove {
     pub scale_key_points: Vec<KeyPoint>,
 
     pub x_position_key_points: Vec<KeyPoint>,
+
+    /// 未声明的字段, 序列化时原样写回.
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(flatten))]
+    pub extra: Extra,
 }
 
 #[cfg_attr(feature = "serialize", derive(Serialize))]
 #[cfg_attr(feature = "deserialize", derive(Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[cfg_attr(
     any(feature = "serialize", feature = "deserialize"),
     serde(rename_all = "camelCase")
@@ -412,16 +501,25 @@ pub struct RizlineChart {
     pub canvas_moves: Vec<CanvasMove>,
 
     pub camera_move: CameraMove,
-}
 
-impl TryInto<chart::Chart> for RizlineChart {
-    type Error = ConvertError;
+    /// 未声明的字段, 序列化时原样写回.
+    #[cfg_attr(any(feature = "serialize", feature = "deserialize"), serde(flatten))]
+    pub extra: Extra,
+}
 
-    fn try_into(self) -> ConvertResult<chart::Chart> {
+impl RizlineChart {
+    /// 转换为 [`chart::Chart`], 并收集所有错误而不是在第一个错误处停止.
+    ///
+    /// 每个错误都是带有原文件中路径的 [`ConvertError::At`].
+    pub fn convert_all(self) -> Result<chart::Chart, Vec<ConvertError>> {
+        let mut errors = Errors::default();
         let [normal, challenge] = self.themes;
-        let bpm = convert_bpm_to_timemap(self.bpm, self.bpm_shifts)?;
+        let bpm = convert_bpm_to_timemap(self.bpm, self.bpm_shifts).unwrap_or_else(|e| {
+            errors.push("bpmShifts", e);
+            Spline::EMPTY
+        });
         info!("chart convert started");
-        Ok(chart::Chart {
+        let chart = chart::Chart {
             themes: vec![normal.convert(false), challenge.convert(true)],
             // 如果challenge_times相互重叠(含 trans_time)则会产生奇怪的结果.
             theme_control: Some(chart::KeyPoint {
@@ -461,30 +559,59 @@ impl TryInto<chart::Chart> for RizlineChart {
                 .lines
                 .into_iter()
                 .enumerate()
-                .map(|(index, line)| line.convert(index))
-                .collect::<Result<Vec<_>, _>>()?,
+                .map(|(index, line)| {
+                    errors.item("lines", index, |errors| line.convert(index, errors))
+                })
+                .collect(),
             canvases: self
                 .canvas_moves
                 .into_iter()
-                .map(|c| c.convert())
-                .collect::<ConvertResult<_>>()?,
-            cam_move: self
-                .camera_move
-                .x_position_key_points
-                .into_iter()
+                .enumerate()
+                .map(|(index, c)| errors.item("canvasMoves", index, |errors| c.convert(errors)))
+                .collect(),
+            cam_move: {
+                errors.path.key("cameraMove");
+                let cam_move = convert_key_points(
+                    self.camera_move.x_position_key_points,
+                    "xPositionKeyPoints",
+                    &mut errors,
+                )
                 .map(|mut k| {
                     k.value = scale_x(k.value);
-                    k.try_into()
+                    k
                 })
-                .collect::<ConvertResult<_>>()?,
-            cam_scale: self
-                .camera_move
-                .scale_key_points
-                .into_iter()
-                .map(TryInto::try_into)
-                .collect::<ConvertResult<_>>()?,
+                .collect();
+                errors.path.0.clear();
+                cam_move
+            },
+            cam_scale: {
+                errors.path.key("cameraMove");
+                let cam_scale = convert_key_points(
+                    self.camera_move.scale_key_points,
+                    "scaleKeyPoints",
+                    &mut errors,
+                )
+                .collect();
+                errors.path.0.clear();
+                cam_scale
+            },
             bpm,
-        })
+        };
+        if errors.errors.is_empty() {
+            Ok(chart)
+        } else {
+            Err(errors.errors)
+        }
+    }
+}
+
+impl TryInto<chart::Chart> for RizlineChart {
+    type Error = ConvertError;
+
+    /// 返回第一个错误, 需要所有错误时使用 [`RizlineChart::convert_all`].
+    fn try_into(self) -> ConvertResult<chart::Chart> {
+        self.convert_all()
+            .map_err(|errors| errors.into_iter().next().expect("errors are never empty"))
     }
 }
 
@@ -500,3 +627,121 @@ fn convert_bpm_to_timemap(bpm: f32, bpm_shifts: Vec<KeyPoint>) -> ConvertResult<
         })
         .collect())
 }
+
+/// 读取 Rizline 谱面文件, 出错时给出每个错误在文件中的路径和行列号.
+///
+/// 旧版本的谱面会先被升级, 见 [`from_json_versioned`].
+///
+/// JSON 本身有误时只能报告第一个错误. `collect_all` 为真时报告所有转换错误, 否则只报告第一个.
+#[cfg(feature = "deserialize")]
+pub fn load(text: &[u8], collect_all: bool) -> Result<chart::Chart, Vec<Diagnostic>> {
+    let chart = from_json_versioned(text).map_err(|e| vec![e])?;
+    chart.convert_all().map_err(|mut errors| {
+        if !collect_all {
+            errors.truncate(1);
+        }
+        errors
+            .into_iter()
+            .map(|e| Diagnostic::from_convert_error(text, e))
+            .collect()
+    })
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+    use crate::parse::diagnostic::DiagnosticKind;
+
+    fn chart_json(line_points: &str, notes: &str) -> String {
+        let color = r#"{"r": 255, "g": 255, "b": 255, "a": 255}"#;
+        let key_point = r#"{"time": 0.0, "value": 1.0, "easeType": 0, "floorPosition": 0.0}"#;
+        format!(
+            r#"{{
+                "fileVersion": 1,
+                "songsName": "test",
+                "themes": [{{"colorsList": [{color}, {color}, {color}]}}, {{"colorsList": [{color}, {color}, {color}]}}],
+                "challengeTimes": [],
+                "bPM": 120.0,
+                "bpmShifts": [{key_point}],
+                "offset": 0.0,
+                "lines": [{{
+                    "linePoints": [{line_points}],
+                    "notes": [{notes}],
+                    "judgeRingColor": [],
+                    "lineColor": [],
+                    "layer": 2
+                }}],
+                "canvasMoves": [{{
+                    "index": 0, "xPositionKeyPoints": [{key_point}],
+                    "speedKeyPoints": [{key_point}], "rotation": []
+                }}],
+                "cameraMove": {{
+                    "scaleKeyPoints": [{key_point}], "xPositionKeyPoints": [{key_point}],
+                    "yPositionKeyPoints": [{key_point}]
+                }},
+                "newField": {{"nested": [1, 2]}}
+            }}"#
+        )
+    }
+
+    fn line_point(ease_type: u8) -> String {
+        format!(
+            r#"{{"time": 0.0, "xPosition": 0.5, "color": {{"r": 255, "g": 255, "b": 255, "a": 255}},
+                "easeType": {ease_type}, "canvasIndex": 0, "floorPosition": 0.0, "alpha": 0.5}}"#
+        )
+    }
+
+    #[test]
+    fn test_unknown_fields_round_trip() {
+        let json = chart_json(
+            &line_point(0),
+            r#"{"type": 0, "time": 1.0, "floorPosition": 0.0, "otherInformations": [], "sound": "a"}"#,
+        );
+        let original: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let chart: RizlineChart = serde_json::from_str(&json).unwrap();
+        assert_eq!(chart.extra["newField"]["nested"][1], 2);
+        assert_eq!(chart.lines[0].extra["layer"], 2);
+        assert_eq!(chart.lines[0].line_points[0].extra["alpha"], 0.5);
+        assert_eq!(chart.lines[0].notes[0].extra["sound"], "a");
+        assert!(chart.canvas_moves[0].extra.contains_key("rotation"));
+        assert!(chart.camera_move.extra.contains_key("yPositionKeyPoints"));
+        assert_eq!(serde_json::to_value(&chart).unwrap(), original);
+        let _: chart::Chart = chart.try_into().unwrap();
+    }
+
+    #[test]
+    fn test_error_locations() {
+        let hold = r#"{"type": 2, "time": 1.0, "floorPosition": 0.0, "otherInformations": []}"#;
+        let json = chart_json(&[line_point(0), line_point(99)].join(",\n"), hold);
+        let line_of = |pattern: &str| json.lines().position(|l| l.contains(pattern)).unwrap() + 1;
+
+        let errors = load(json.as_bytes(), true).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].path.to_string(),
+            "lines[0].linePoints[1].easeType"
+        );
+        assert_eq!(errors[0].position.unwrap().0, line_of("\"easeType\": 99"));
+        assert!(matches!(
+            errors[0].kind,
+            DiagnosticKind::Convert {
+                source: ConvertError::UnknownEaseKind { raw_kind: 99 }
+            }
+        ));
+        assert_eq!(
+            errors[1].path.to_string(),
+            "lines[0].notes[0].otherInformations"
+        );
+        assert_eq!(errors[1].position.unwrap().0, line_of("\"type\": 2"));
+        assert_eq!(load(json.as_bytes(), false).unwrap_err().len(), 1);
+
+        let json = json.replace("\"easeType\": 99", "\"easeType\": \"x\"");
+        let errors = load(json.as_bytes(), true).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].path.to_string(),
+            "lines[0].linePoints[1].easeType"
+        );
+        assert_eq!(errors[0].position.unwrap().0, line_of("\"easeType\": 99"));
+    }
+}
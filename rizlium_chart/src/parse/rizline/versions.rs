@@ -0,0 +1,185 @@
+//! 不同 `fileVersion` 的 Rizline 谱面.
+//!
+//! 旧版本的谱面先在 JSON 层面逐版本升级为当前的格式, 再按当前的结构体读取.
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::parse::{
+    diagnostic::{from_json, from_value, locate, Diagnostic, DiagnosticKind, JsonPath},
+    ConvertError,
+};
+
+use super::RizlineChart;
+
+/// 支持的最新 `fileVersion`, 即 [`RizlineChart`] 的格式.
+pub const LATEST_FILE_VERSION: i64 = 1;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionProbe {
+    file_version: Option<Value>,
+}
+
+/// 升级到下一个版本. 第 `i` 项把版本 `i` 的谱面升级为版本 `i + 1`.
+const MIGRATIONS: [fn(&mut Map<String, Value>); LATEST_FILE_VERSION as usize] = [migrate_v0];
+
+/// 版本 0 (没有 `fileVersion` 字段的早期谱面):
+/// - 只有一个主题, 写作 `theme` 或只有一项的 `themes`;
+/// - 颜色可能写作 `[r, g, b]` 或 `[r, g, b, a]` 数组;
+/// - 可能没有 `challengeTimes` 和 `cameraMove`.
+fn migrate_v0(chart: &mut Map<String, Value>) {
+    let themes = chart
+        .remove("themes")
+        .or_else(|| chart.remove("theme"))
+        .map(|themes| match themes {
+            Value::Array(themes) => themes,
+            theme => vec![theme],
+        })
+        .unwrap_or_default();
+    // 挑战主题缺失时沿用普通主题.
+    let themes: Vec<_> = match &themes[..] {
+        [normal] => vec![normal.clone(), normal.clone()],
+        _ => themes,
+    };
+    chart.insert("themes".into(), themes.into());
+    chart.entry("challengeTimes").or_insert_with(|| json!([]));
+    chart.entry("cameraMove").or_insert_with(|| {
+        let constant =
+            |value: f32| json!([{"time": 0., "value": value, "easeType": 0, "floorPosition": 0.}]);
+        json!({"scaleKeyPoints": constant(1.), "xPositionKeyPoints": constant(0.)})
+    });
+    for value in chart.values_mut() {
+        upgrade_colors(value);
+    }
+}
+
+fn upgrade_color(color: &mut Value) {
+    let Value::Array(channels) = color else {
+        return;
+    };
+    let mut channels = channels.clone();
+    if channels.len() == 3 {
+        channels.push(255.into());
+    }
+    if let [r, g, b, a] = &channels[..] {
+        *color = json!({"r": r, "g": g, "b": b, "a": a});
+    }
+}
+
+/// 把所有颜色字段中的数组形式改写为对象.
+fn upgrade_colors(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match key.as_str() {
+                    "color" | "startColor" | "endColor" => upgrade_color(value),
+                    "colorsList" => {
+                        if let Value::Array(colors) = value {
+                            colors.iter_mut().for_each(upgrade_color);
+                        }
+                    }
+                    _ => upgrade_colors(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(upgrade_colors),
+        _ => (),
+    }
+}
+
+/// 按 `fileVersion` 读取谱面, 旧版本的谱面会被升级为当前的格式.
+///
+/// 比 [`LATEST_FILE_VERSION`] 新的版本会报错, 而不是尝试按当前的格式读取.
+pub fn from_json_versioned(text: &[u8]) -> Result<RizlineChart, Diagnostic> {
+    let Ok(probe) = serde_json::from_slice::<VersionProbe>(text) else {
+        // 语法错误等, 由完整的反序列化报告.
+        return from_json(text);
+    };
+    let version = match probe.file_version {
+        None => 0,
+        Some(version) => match version.as_i64() {
+            Some(version) => version,
+            // 类型错误由完整的反序列化报告.
+            None => return from_json(text),
+        },
+    };
+    if version == LATEST_FILE_VERSION {
+        return from_json(text);
+    }
+    if !(0..LATEST_FILE_VERSION).contains(&version) {
+        let mut path = JsonPath::default();
+        path.key("fileVersion");
+        return Err(Diagnostic {
+            position: locate(text, &path),
+            path,
+            kind: DiagnosticKind::Convert {
+                source: ConvertError::UnsupportedFileVersion {
+                    version,
+                    latest: LATEST_FILE_VERSION,
+                },
+            },
+        });
+    }
+    let mut value: Value = from_json(text)?;
+    if let Value::Object(chart) = &mut value {
+        for migrate in &MIGRATIONS[version as usize..] {
+            migrate(chart);
+        }
+        chart.insert("fileVersion".into(), LATEST_FILE_VERSION.into());
+    }
+    from_value(text, value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_legacy_chart() {
+        let key_point = r#"{"time": 0.0, "value": 1.0, "easeType": 0, "floorPosition": 0.0}"#;
+        let json = format!(
+            r#"{{
+                "songsName": "old",
+                "theme": {{"colorsList": [[0, 0, 0], [255, 255, 255, 128], [1, 2, 3]]}},
+                "bPM": 120.0,
+                "bpmShifts": [{key_point}],
+                "offset": 0.0,
+                "lines": [{{
+                    "linePoints": [{{
+                        "time": 0.0, "xPosition": 0.5, "color": [10, 20, 30], "easeType": 0,
+                        "canvasIndex": 0, "floorPosition": 0.0
+                    }}],
+                    "notes": [],
+                    "judgeRingColor": [{{"startColor": [1, 1, 1], "endColor": [2, 2, 2, 2], "time": 0.0}}],
+                    "lineColor": []
+                }}],
+                "canvasMoves": [{{
+                    "index": 0, "xPositionKeyPoints": [{key_point}], "speedKeyPoints": [{key_point}]
+                }}]
+            }}"#
+        );
+        let chart = from_json_versioned(json.as_bytes()).unwrap();
+        assert_eq!(chart.file_version, 1);
+        assert_eq!(chart.themes[1].colors_list[1].a, 128);
+        assert_eq!(chart.lines[0].line_points[0].color.b, 30);
+        assert_eq!(chart.lines[0].line_points[0].color.a, 255);
+        assert_eq!(chart.lines[0].judge_ring_color[0].end_color.a, 2);
+        assert_eq!(chart.camera_move.scale_key_points[0].value, 1.);
+        let _: crate::chart::Chart = chart.try_into().unwrap();
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let error = from_json_versioned(b"{\n  \"fileVersion\": 7\n}")
+            .err()
+            .unwrap();
+        assert_eq!(error.path.to_string(), "fileVersion");
+        assert_eq!(error.position, Some((2, 18)));
+        assert!(matches!(
+            error.kind,
+            DiagnosticKind::Convert {
+                source: ConvertError::UnsupportedFileVersion { version: 7, .. }
+            }
+        ));
+    }
+}
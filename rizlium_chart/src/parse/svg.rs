@@ -0,0 +1,527 @@
+//! 把矢量软件中画出的 SVG 路径导入为线的形状.
+//!
+//! 路径的横轴对应线的横坐标, 纵轴 (向上) 通过所选画布的高度对应时间.
+//! 只读取路径数据本身, 不处理 `transform` 等属性.
+use snafu::{ensure, OptionExt};
+use strum::IntoEnumIterator;
+
+use crate::chart::{ChartCache, ColorRGBA, EasingId, KeyPoint, Line, LinePointData, Spline, Tween};
+
+use super::{
+    CanvasUnavailableSnafu, ConvertResult, InvalidSvgPathSnafu, NoSvgPathSnafu,
+    NonMonotonicPathSnafu,
+};
+
+/// 三次贝塞尔曲线的四个控制点, 直线和二次曲线都会转换为三次曲线.
+pub type Cubic = [[f32; 2]; 4];
+
+/// 拟合时每段曲线的采样数.
+const SAMPLES: usize = 16;
+/// 误差过大时最多细分的次数.
+const MAX_DEPTH: usize = 6;
+
+#[derive(Debug, Clone)]
+pub struct SvgImportConfig {
+    /// 纵轴对应的画布.
+    pub canvas: usize,
+    /// 路径最下方的点所处的时间.
+    pub start_time: f32,
+    /// 路径坐标到谱面坐标的缩放.
+    pub scale: f32,
+    /// 路径中 x = 0 对应的横坐标.
+    pub x_offset: f32,
+    /// 拟合允许的最大横向误差, 超过时曲线会被细分.
+    pub tolerance: f32,
+    pub color: ColorRGBA,
+}
+
+impl Default for SvgImportConfig {
+    fn default() -> Self {
+        Self {
+            canvas: 0,
+            start_time: 0.,
+            scale: 1.,
+            x_offset: 0.,
+            tolerance: 2.,
+            color: ColorRGBA::WHITE,
+        }
+    }
+}
+
+/// 找出 SVG 文件中所有 `<path>` 的 `d` 属性.
+///
+/// 不含 `<` 的输入被视为路径数据本身.
+pub fn path_data(svg: &str) -> Vec<&str> {
+    if !svg.contains('<') {
+        return vec![svg];
+    }
+    svg.split("<path")
+        .skip(1)
+        .filter_map(|element| {
+            let element = &element[..element.find('>').unwrap_or(element.len())];
+            let start = element
+                .match_indices("d=")
+                .map(|(index, _)| index)
+                .find(|&index| index == 0 || element.as_bytes()[index - 1].is_ascii_whitespace())?
+                + 2;
+            let quote = *element
+                .as_bytes()
+                .get(start)
+                .filter(|quote| matches!(quote, b'"' | b'\''))?;
+            let rest = &element[start + 1..];
+            Some(&rest[..rest.find(char::from(quote))?])
+        })
+        .collect()
+}
+
+struct PathParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl PathParser<'_> {
+    fn skip_separators(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace() || *b == b',')
+        {
+            self.pos += 1;
+        }
+    }
+    fn at_number(&mut self) -> bool {
+        self.skip_separators();
+        self.bytes
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.'))
+    }
+    fn number(&mut self) -> ConvertResult<f32> {
+        self.skip_separators();
+        let start = self.pos;
+        let mut seen_dot = false;
+        let mut seen_exp = false;
+        while let Some(&b) = self.bytes.get(self.pos) {
+            let accepted = match b {
+                b'0'..=b'9' => true,
+                b'-' | b'+' => self.pos == start || matches!(self.bytes[self.pos - 1], b'e' | b'E'),
+                // `1.5.5` 是 `1.5` 和 `.5` 两个数.
+                b'.' if !seen_dot && !seen_exp => {
+                    seen_dot = true;
+                    true
+                }
+                b'e' | b'E' if !seen_exp && self.pos > start => {
+                    seen_exp = true;
+                    true
+                }
+                _ => false,
+            };
+            if !accepted {
+                break;
+            }
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .context(InvalidSvgPathSnafu {
+                position: start,
+                reason: "expected number",
+            })
+    }
+    fn point(&mut self, relative_to: Option<[f32; 2]>) -> ConvertResult<[f32; 2]> {
+        let [dx, dy] = relative_to.unwrap_or_default();
+        Ok([self.number()? + dx, self.number()? + dy])
+    }
+}
+
+fn line_to_cubic(from: [f32; 2], to: [f32; 2]) -> Cubic {
+    [from, lerp2(from, to, 1. / 3.), lerp2(from, to, 2. / 3.), to]
+}
+
+fn quad_to_cubic(from: [f32; 2], control: [f32; 2], to: [f32; 2]) -> Cubic {
+    [
+        from,
+        lerp2(from, control, 2. / 3.),
+        lerp2(to, control, 2. / 3.),
+        to,
+    ]
+}
+
+fn lerp2(a: [f32; 2], b: [f32; 2], t: f32) -> [f32; 2] {
+    [f32::lerp(a[0], b[0], t), f32::lerp(a[1], b[1], t)]
+}
+
+fn reflect(control: [f32; 2], around: [f32; 2]) -> [f32; 2] {
+    [2. * around[0] - control[0], 2. * around[1] - control[1]]
+}
+
+/// 解析路径数据, 每个子路径 (`M` 开始) 为一组首尾相连的曲线.
+///
+/// 不支持圆弧 (`A`), 需要先在矢量软件中转换为曲线.
+pub fn parse_path(data: &str) -> ConvertResult<Vec<Vec<Cubic>>> {
+    let mut parser = PathParser {
+        bytes: data.as_bytes(),
+        pos: 0,
+    };
+    let mut subpaths = Vec::new();
+    let mut current: Vec<Cubic> = Vec::new();
+    let mut pos = [0.; 2];
+    let mut start = pos;
+    let mut command = None;
+    // 上一段曲线的第二个控制点, 用于 `S` 和 `T`.
+    let mut last_cubic = None;
+    let mut last_quad = None;
+    loop {
+        parser.skip_separators();
+        let Some(&byte) = parser.bytes.get(parser.pos) else {
+            break;
+        };
+        if byte.is_ascii_alphabetic() {
+            parser.pos += 1;
+            command = Some(byte);
+        } else {
+            // 省略命令时重复上一个命令, `M` 之后的坐标视为 `L`.
+            command = match command {
+                Some(b'M') => Some(b'L'),
+                Some(b'm') => Some(b'l'),
+                Some(b'Z' | b'z') | None => None,
+                other => other,
+            };
+        }
+        let Some(command) = command else {
+            return InvalidSvgPathSnafu {
+                position: parser.pos,
+                reason: "expected command",
+            }
+            .fail();
+        };
+        let relative = command.is_ascii_lowercase().then_some(pos);
+        let (mut next_cubic, mut next_quad) = (None, None);
+        match command.to_ascii_uppercase() {
+            b'M' => {
+                if !current.is_empty() {
+                    subpaths.push(std::mem::take(&mut current));
+                }
+                pos = parser.point(relative)?;
+                start = pos;
+            }
+            b'L' => {
+                let to = parser.point(relative)?;
+                current.push(line_to_cubic(pos, to));
+                pos = to;
+            }
+            b'H' => {
+                let x = parser.number()? + relative.map_or(0., |[x, _]| x);
+                let to = [x, pos[1]];
+                current.push(line_to_cubic(pos, to));
+                pos = to;
+            }
+            b'V' => {
+                let y = parser.number()? + relative.map_or(0., |[_, y]| y);
+                let to = [pos[0], y];
+                current.push(line_to_cubic(pos, to));
+                pos = to;
+            }
+            b'C' | b'S' => {
+                let first = if command.eq_ignore_ascii_case(&b'C') {
+                    parser.point(relative)?
+                } else {
+                    last_cubic.map_or(pos, |control| reflect(control, pos))
+                };
+                let second = parser.point(relative)?;
+                let to = parser.point(relative)?;
+                current.push([pos, first, second, to]);
+                next_cubic = Some(second);
+                pos = to;
+            }
+            b'Q' | b'T' => {
+                let control = if command.eq_ignore_ascii_case(&b'Q') {
+                    parser.point(relative)?
+                } else {
+                    last_quad.map_or(pos, |control| reflect(control, pos))
+                };
+                let to = parser.point(relative)?;
+                current.push(quad_to_cubic(pos, control, to));
+                next_quad = Some(control);
+                pos = to;
+            }
+            b'Z' => {
+                if pos != start {
+                    current.push(line_to_cubic(pos, start));
+                }
+                pos = start;
+            }
+            b'A' => {
+                return InvalidSvgPathSnafu {
+                    position: parser.pos - 1,
+                    reason: "arcs are not supported, convert them to curves first",
+                }
+                .fail();
+            }
+            _ => {
+                return InvalidSvgPathSnafu {
+                    position: parser.pos - 1,
+                    reason: "unknown command",
+                }
+                .fail();
+            }
+        }
+        (last_cubic, last_quad) = (next_cubic, next_quad);
+        if matches!(command, b'Z' | b'z') && parser.at_number() {
+            return InvalidSvgPathSnafu {
+                position: parser.pos,
+                reason: "unexpected number after close path",
+            }
+            .fail();
+        }
+    }
+    if !current.is_empty() {
+        subpaths.push(current);
+    }
+    Ok(subpaths)
+}
+
+fn eval(curve: &Cubic, t: f32) -> [f32; 2] {
+    let [a, b, c, d] = *curve;
+    let ab = lerp2(a, b, t);
+    let bc = lerp2(b, c, t);
+    let cd = lerp2(c, d, t);
+    lerp2(lerp2(ab, bc, t), lerp2(bc, cd, t), t)
+}
+
+fn split(curve: &Cubic, t: f32) -> (Cubic, Cubic) {
+    let [a, b, c, d] = *curve;
+    let ab = lerp2(a, b, t);
+    let bc = lerp2(b, c, t);
+    let cd = lerp2(c, d, t);
+    let abc = lerp2(ab, bc, t);
+    let bcd = lerp2(bc, cd, t);
+    let mid = lerp2(abc, bcd, t);
+    ([a, ab, abc, mid], [mid, bcd, cd, d])
+}
+
+/// 在纵坐标的极值处切开曲线, 使每段的纵坐标都单调.
+fn split_monotonic(curve: Cubic, out: &mut Vec<Cubic>) {
+    let [p0, p1, p2, p3] = curve.map(|[_, y]| y);
+    // 纵坐标导数 `a t^2 + b t + c` 的系数.
+    let a = 3. * (-p0 + 3. * p1 - 3. * p2 + p3);
+    let b = 6. * (p0 - 2. * p1 + p2);
+    let c = 3. * (p1 - p0);
+    let mut roots = if a.abs() < 1e-6 {
+        if b.abs() < 1e-6 {
+            vec![]
+        } else {
+            vec![-c / b]
+        }
+    } else {
+        let discriminant = b * b - 4. * a * c;
+        if discriminant < 0. {
+            vec![]
+        } else {
+            let sqrt = discriminant.sqrt();
+            vec![(-b - sqrt) / (2. * a), (-b + sqrt) / (2. * a)]
+        }
+    };
+    roots.retain(|t| *t > 1e-4 && *t < 1. - 1e-4);
+    roots.sort_by(f32::total_cmp);
+    let mut rest = curve;
+    let mut consumed = 0.;
+    for t in roots {
+        let (head, tail) = split(&rest, (t - consumed) / (1. - consumed));
+        out.push(head);
+        rest = tail;
+        consumed = t;
+    }
+    out.push(rest);
+}
+
+struct Fitter<'a> {
+    cache: &'a ChartCache,
+    config: &'a SvgImportConfig,
+}
+
+impl Fitter<'_> {
+    fn time(&self, y: f32) -> ConvertResult<f32> {
+        self.cache
+            .canvas_y_to_time(self.config.canvas, y)
+            .context(CanvasUnavailableSnafu {
+                canvas: self.config.canvas,
+            })
+    }
+
+    /// 为纵坐标单调递增的 `curve` 选择误差最小的缓动, 误差过大时细分.
+    fn fit(&self, curve: &Cubic, depth: usize, out: &mut Vec<KeyPoint<f32>>) -> ConvertResult<()> {
+        let samples = (0..=SAMPLES)
+            .map(|i| {
+                let [x, y] = eval(curve, i as f32 / SAMPLES as f32);
+                Ok([self.time(y)?, x])
+            })
+            .collect::<ConvertResult<Vec<_>>>()?;
+        let [start_time, start_x] = samples[0];
+        let [end_time, end_x] = samples[SAMPLES];
+        let error = |ease_type: EasingId| {
+            samples
+                .iter()
+                .map(|&[time, x]| {
+                    let t = crate::chart::invlerp(start_time, end_time, time);
+                    (f32::ease(start_x, end_x, t, ease_type) - x).abs()
+                })
+                .fold(0., f32::max)
+        };
+        let (ease_type, error) = if end_time - start_time <= f32::EPSILON {
+            (EasingId::Linear, 0.)
+        } else {
+            EasingId::iter()
+                .filter(|ease| {
+                    !matches!(ease, EasingId::Start | EasingId::End | EasingId::AnimCurve)
+                })
+                .map(|ease| (ease, error(ease)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap()
+        };
+        if error > self.config.tolerance && depth < MAX_DEPTH {
+            let (head, tail) = split(curve, 0.5);
+            self.fit(&head, depth + 1, out)?;
+            return self.fit(&tail, depth + 1, out);
+        }
+        out.push(KeyPoint {
+            time: start_time,
+            value: start_x,
+            ease_type,
+            relevant: (),
+        });
+        Ok(())
+    }
+}
+
+/// 把 SVG 文件或路径数据中的每个子路径转换为一条线.
+///
+/// 子路径可以从上往下或从下往上画, 但不能在纵向上折返.
+pub fn import(svg: &str, cache: &ChartCache, config: &SvgImportConfig) -> ConvertResult<Vec<Line>> {
+    let mut subpaths = Vec::new();
+    for data in path_data(svg) {
+        subpaths.extend(parse_path(data)?);
+    }
+    ensure!(!subpaths.is_empty(), NoSvgPathSnafu);
+    let bottom = subpaths
+        .iter()
+        .flatten()
+        .flatten()
+        .map(|[_, y]| *y)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let base = cache
+        .canvas_y_at(config.canvas, config.start_time)
+        .context(CanvasUnavailableSnafu {
+            canvas: config.canvas,
+        })?;
+    // SVG 的纵轴向下, 画布高度向上.
+    let to_chart = |[x, y]: [f32; 2]| {
+        [
+            x.mul_add(config.scale, config.x_offset),
+            (bottom - y).mul_add(config.scale, base),
+        ]
+    };
+    let fitter = Fitter { cache, config };
+    subpaths
+        .into_iter()
+        .enumerate()
+        .map(|(subpath, curves)| {
+            let mut pieces = Vec::new();
+            for curve in curves {
+                split_monotonic(curve.map(to_chart), &mut pieces);
+            }
+            let rising = |piece: &Cubic| piece[3][1] - piece[0][1];
+            let up = pieces.iter().any(|piece| rising(piece) > f32::EPSILON);
+            let down = pieces.iter().any(|piece| rising(piece) < -f32::EPSILON);
+            ensure!(!(up && down), NonMonotonicPathSnafu { subpath });
+            if down {
+                pieces.reverse();
+                pieces.iter_mut().for_each(|piece| piece.reverse());
+            }
+            let mut points = Vec::new();
+            for piece in &pieces {
+                fitter.fit(piece, 0, &mut points)?;
+            }
+            let [x, y] = pieces.last().expect("subpath is never empty")[3];
+            points.push(KeyPoint {
+                time: fitter.time(y)?,
+                value: x,
+                ease_type: EasingId::Linear,
+                relevant: (),
+            });
+            let mut line: Line = points
+                .into_iter()
+                .map(|point| {
+                    point.with_relevant(LinePointData {
+                        canvas: config.canvas,
+                        color: config.color,
+                        ..Default::default()
+                    })
+                })
+                .collect();
+            line.ring_color = Spline::from(vec![KeyPoint {
+                time: 0.,
+                value: config.color,
+                ease_type: EasingId::Start,
+                relevant: (),
+            }]);
+            Ok(line)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{chart::Chart, fixture};
+
+    /// 120 BPM, 画布速度为每秒 100, 因此每拍对应 50 的高度.
+    fn chart() -> Chart {
+        let mut chart = fixture::chart(vec![]);
+        chart.canvases[0].speed = Spline::constant_speed(100.);
+        chart
+    }
+
+    #[test]
+    fn test_parse_path() {
+        let subpaths = parse_path("M0 0 10,0 h5 v-5 Z m1-1.5.5.5c0 0 1 1 2 2 s3 3 4 4").unwrap();
+        assert_eq!(subpaths.len(), 2);
+        assert_eq!(subpaths[0].len(), 4);
+        assert_eq!(subpaths[0][2][3], [15., -5.]);
+        assert_eq!(subpaths[0][3][3], [0., 0.]);
+        assert_eq!(subpaths[1][0][0], [1., -1.5]);
+        assert_eq!(subpaths[1][2][1], [4.5, 2.]);
+        assert_eq!(subpaths[1][2][3], [7.5, 5.]);
+        assert!(parse_path("M0 0 A1 1 0 0 1 2 2").is_err());
+        assert!(parse_path("0 0").is_err());
+        assert_eq!(
+            path_data(r#"<svg><path fill="none" d="M0 0L1 1"/><path id='a' d='M2 2'/></svg>"#),
+            ["M0 0L1 1", "M2 2"]
+        );
+        assert!(path_data("<svg><path d=é/></svg>").is_empty());
+        assert!(path_data("<svg><path d=M0/></svg>").is_empty());
+    }
+
+    #[test]
+    fn test_import_svg() {
+        let chart = chart();
+        let cache = ChartCache::from_chart(&chart);
+        let config = SvgImportConfig {
+            start_time: 1.,
+            ..Default::default()
+        };
+        // 从上往下画的直线, 与从下往上画的结果相同.
+        let lines = import("M0 -100 L50 0", &cache, &config).unwrap();
+        let points: Vec<_> = lines[0].points.iter().map(|p| p.as_slice()).collect();
+        assert_eq!(points, [[1., 50.], [3., 0.]]);
+        assert_eq!(lines[0].points.points()[0].ease_type, EasingId::Linear);
+
+        // 纵向匀速, 横向为 t^2 的曲线.
+        let lines = import("M0 0 C0 -33.333 33.333 -66.667 100 -100", &cache, &config).unwrap();
+        assert_eq!(lines[0].points.len(), 2);
+        assert_eq!(lines[0].points.points()[0].ease_type, EasingId::QuadIn);
+
+        assert!(import("M0 0 L10 -10 L20 0", &cache, &config).is_err());
+        assert!(import("<svg></svg>", &cache, &config).is_err());
+    }
+}
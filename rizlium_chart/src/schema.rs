@@ -0,0 +1,83 @@
+//! 由谱面类型生成的 JSON Schema, 供其他语言的工具校验谱面文件.
+//!
+//! 生成的文件位于 `schemas/` 下, 文件名中带有格式的版本.
+//! 修改谱面类型后需要重新生成:
+//! `RIZLIUM_UPDATE_SCHEMA=1 cargo test -p rizlium_chart schema`.
+use schemars::{schema_for, Schema};
+use serde_json::json;
+
+use crate::chart::Chart;
+
+/// 原生谱面格式的版本, 格式发生不兼容的变化时增加.
+pub const CHART_FORMAT_VERSION: u32 = 1;
+
+fn versioned(mut schema: Schema, name: &str, version: i64) -> Schema {
+    schema.insert(
+        "$id".into(),
+        json!(format!("{name}.v{version}.schema.json")),
+    );
+    schema.insert("x-format-version".into(), json!(version));
+    schema
+}
+
+/// 原生 Rizlium 谱面 ([`Chart`]) 的 Schema.
+pub fn chart_schema() -> Schema {
+    versioned(schema_for!(Chart), "rizlium", CHART_FORMAT_VERSION.into())
+}
+
+/// Rizline 谱面的 Schema, 版本与 [`LATEST_FILE_VERSION`](crate::parse::rizline::LATEST_FILE_VERSION) 一致.
+#[cfg(feature = "rizline")]
+pub fn rizline_schema() -> Schema {
+    use crate::parse::rizline::{RizlineChart, LATEST_FILE_VERSION};
+    versioned(schema_for!(RizlineChart), "rizline", LATEST_FILE_VERSION)
+}
+
+/// 所有 Schema 的文件名与内容.
+pub fn schema_files() -> Vec<(String, String)> {
+    let mut files = vec![(
+        format!("rizlium.v{CHART_FORMAT_VERSION}.schema.json"),
+        chart_schema(),
+    )];
+    #[cfg(feature = "rizline")]
+    files.push((
+        format!(
+            "rizline.v{}.schema.json",
+            crate::parse::rizline::LATEST_FILE_VERSION
+        ),
+        rizline_schema(),
+    ));
+    files
+        .into_iter()
+        .map(|(name, schema)| {
+            let mut text = serde_json::to_string_pretty(&schema).unwrap();
+            text.push('\n');
+            (name, text)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    #[test]
+    fn test_schema_up_to_date() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("schemas");
+        let update = std::env::var_os("RIZLIUM_UPDATE_SCHEMA").is_some();
+        for (name, text) in schema_files() {
+            let path = dir.join(&name);
+            if update {
+                fs::create_dir_all(&dir).unwrap();
+                fs::write(&path, text).unwrap();
+                continue;
+            }
+            let saved = fs::read_to_string(&path).unwrap_or_default();
+            assert!(
+                saved == text,
+                "{name} is out of date, regenerate it with `RIZLIUM_UPDATE_SCHEMA=1 cargo test -p rizlium_chart schema`"
+            );
+        }
+    }
+}
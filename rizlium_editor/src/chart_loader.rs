@@ -1,6 +1,6 @@
 use std::{
     borrow::Cow,
-    io::{Cursor, Read},
+    io::{Cursor, Read, Seek},
 };
 
 use bevy::{
@@ -8,12 +8,20 @@ use bevy::{
     tasks::{IoTaskPool, Task},
 };
 use bevy_kira_audio::{prelude::StaticSoundData, AudioSource};
-use rizlium_chart::prelude::{Chart, RizlineChart};
+use rizlium_chart::{
+    parse::{
+        diagnostic::{from_json, Diagnostic},
+        lanes::{LaneChart, LaneConfig},
+        phigros::PhigrosChart,
+        rizline, ConvertWarning,
+    },
+    prelude::Chart,
+};
 use serde::Deserialize;
 use snafu::{ResultExt, Snafu};
 use zip::ZipArchive;
 
-use rizlium_render::{GameAudioSource, GameChart};
+use rizlium_render::{GameAudioSource, GameChart, GameKeySounds};
 
 pub struct ChartLoadingPlugin;
 
@@ -45,6 +53,8 @@ pub enum ChartFormat {
     #[default]
     Rizline,
     Rizlium,
+    Phigros,
+    OsuMania,
 }
 
 #[derive(Event)]
@@ -53,6 +63,8 @@ pub struct LoadChartEvent(pub String);
 #[derive(Event)]
 pub enum ChartLoadingEvent {
     Success(String),
+    /// 谱面已加载, 但转换时有无法表示的内容被丢弃. 在 [`Self::Success`] 之前发送.
+    Warnings(Vec<ConvertWarning>),
     Error(ChartLoadingError),
 }
 
@@ -64,7 +76,10 @@ impl ChartLoadingEvent {
 
 pub struct BundledGameChart {
     music: AudioSource,
+    /// 音符的击打音效, 以谱面包中的路径索引.
+    key_sounds: Vec<(String, AudioSource)>,
     chart: Chart,
+    warnings: Vec<ConvertWarning>,
     path: String,
     _info: ChartInfo,
 }
@@ -84,7 +99,12 @@ pub enum ChartLoadingError {
         source: zip::result::ZipError,
     },
     #[snafu(display("Chart format is invalid: {}", source))]
-    ChartFormatInvalid { source: serde_json::Error },
+    ChartFormatInvalid { source: Diagnostic },
+    #[snafu(display(
+        "Chart is invalid:\n{}",
+        diagnostics.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n")
+    ))]
+    ChartInvalid { diagnostics: Vec<Diagnostic> },
     #[snafu(display("Chart info format is invalid: {}", source))]
     InfoFormatInvalid { source: serde_yaml::Error },
     #[snafu(display("Failed to convert chart: {}", source))]
@@ -93,6 +113,79 @@ pub enum ChartLoadingError {
     },
     #[snafu(display("Failed to convert music: {}", source))]
     MusicConvertingFailed { source: kira::sound::FromFileError },
+    #[snafu(display("Failed to convert key sound {}: {}", path, source))]
+    KeySoundConvertingFailed {
+        path: String,
+        source: kira::sound::FromFileError,
+    },
+}
+
+/// 读取谱面包中的 `info.yml`.
+fn read_info<R: Read + Seek>(res: &mut ZipArchive<R>) -> Result<ChartInfo, ChartLoadingError> {
+    let info_file = res.by_name("info.yml").context(NoFileInZipSnafu {
+        file_name: "info.yml",
+    })?;
+    serde_yaml::from_reader(info_file).context(InfoFormatInvalidSnafu)
+}
+
+/// 读取并转换谱面包中的谱面, 同时返回转换时的警告.
+fn read_chart<R: Read + Seek>(
+    res: &mut ZipArchive<R>,
+    info: &ChartInfo,
+) -> Result<(Chart, Vec<ConvertWarning>), ChartLoadingError> {
+    let chart_path = &info.chart_path;
+    let mut chart_data = Vec::new();
+    res.by_name(chart_path)
+        .context(NoFileInZipSnafu {
+            file_name: chart_path.clone(),
+        })?
+        .read_to_end(&mut chart_data)
+        .context(ReadingFileFailedSnafu)?;
+    let chart = match info.format {
+        // 报告所有错误, 避免修一处才能看到下一处.
+        ChartFormat::Rizline => rizline::load(&chart_data, true)
+            .map_err(|diagnostics| ChartLoadingError::ChartInvalid { diagnostics })?,
+        ChartFormat::Rizlium => from_json(&chart_data).context(ChartFormatInvalidSnafu)?,
+        ChartFormat::Phigros => {
+            let chart: PhigrosChart = from_json(&chart_data).context(ChartFormatInvalidSnafu)?;
+            return chart.convert().context(ChartConvertingFailedSnafu);
+        }
+        ChartFormat::OsuMania => {
+            let text = String::from_utf8_lossy(&chart_data);
+            LaneChart::parse_osu(&text)
+                .and_then(|lanes| lanes.into_chart(&LaneConfig::default()))
+                .context(ChartConvertingFailedSnafu)?
+        }
+    };
+    Ok((chart, Vec::new()))
+}
+
+/// 只重新读取谱面包中的谱面, 不加载音频.
+///
+/// 转换警告已在首次加载时报告过, 这里不再返回.
+pub fn load_bundle_chart(path: &str) -> Result<Chart, ChartLoadingError> {
+    let mut file = std::fs::read(path).context(ReadingFileFailedSnafu)?;
+    let mut res =
+        ZipArchive::new(Cursor::new(file.as_mut_slice())).context(UnzipFileFailedSnafu)?;
+    let info = read_info(&mut res)?;
+    read_chart(&mut res, &info).map(|(chart, _)| chart)
+}
+
+fn read_key_sound<R: Read + Seek>(
+    res: &mut ZipArchive<R>,
+    path: &str,
+) -> Result<AudioSource, ChartLoadingError> {
+    let mut data = Vec::new();
+    res.by_name(path)
+        .context(NoFileInZipSnafu {
+            file_name: path.to_owned(),
+        })?
+        .read_to_end(&mut data)
+        .context(ReadingFileFailedSnafu)?;
+    Ok(bevy_kira_audio::AudioSource {
+        sound: StaticSoundData::from_cursor(Cursor::new(data))
+            .context(KeySoundConvertingFailedSnafu { path })?,
+    })
 }
 
 fn load_chart(path: String, mut pending: ResMut<PendingChart>) {
@@ -102,28 +195,9 @@ fn load_chart(path: String, mut pending: ResMut<PendingChart>) {
             .context(ReadingFileFailedSnafu)?;
         let mut res =
             ZipArchive::new(Cursor::new(file.as_mut_slice())).context(UnzipFileFailedSnafu)?;
-        let info_file = res.by_name("info.yml").context(NoFileInZipSnafu {
-            file_name: "info.yml",
-        })?;
-        let info: ChartInfo = serde_yaml::from_reader(info_file).context(InfoFormatInvalidSnafu)?;
-        let chart_path = &info.chart_path;
+        let info = read_info(&mut res)?;
         let music_path = &info.music_path;
-        let chart: Chart = match info.format {
-            ChartFormat::Rizline => {
-                let chart: RizlineChart =
-                    serde_json::from_reader(res.by_name(chart_path).context(NoFileInZipSnafu {
-                        file_name: chart_path.clone(),
-                    })?)
-                    .context(ChartFormatInvalidSnafu)?;
-                chart.try_into().context(ChartConvertingFailedSnafu)?
-            }
-            ChartFormat::Rizlium => {
-                serde_json::from_reader(res.by_name(chart_path).context(NoFileInZipSnafu {
-                    file_name: chart_path.clone(),
-                })?)
-                .context(ChartFormatInvalidSnafu)?
-            }
-        };
+        let (chart, warnings) = read_chart(&mut res, &info)?;
         let mut sound_data = Vec::new();
         res.by_name(music_path)
             .context(NoFileInZipSnafu {
@@ -135,9 +209,19 @@ fn load_chart(path: String, mut pending: ResMut<PendingChart>) {
             sound: StaticSoundData::from_cursor(Cursor::new(sound_data))
                 .context(MusicConvertingFailedSnafu)?,
         };
+        let mut key_sounds = Vec::new();
+        for path in chart.key_sounds() {
+            // 单个音效缺失或无法解码时只跳过它, 不影响谱面的加载.
+            match read_key_sound(&mut res, path) {
+                Ok(sound) => key_sounds.push((path.to_owned(), sound)),
+                Err(err) => warn!("skipping key sound: {err}"),
+            }
+        }
         Ok(BundledGameChart {
             music,
+            key_sounds,
             chart,
+            warnings,
             path,
             _info: info,
         })
@@ -188,7 +272,16 @@ fn unpack_chart(
             commands.insert_resource(GameChart::new(bundle.chart));
             let audio_handle = audio_sources.add(bundle.music);
             commands.insert_resource(GameAudioSource(audio_handle));
+            let key_sounds = bundle
+                .key_sounds
+                .into_iter()
+                .map(|(path, sound)| (path, audio_sources.add(sound)))
+                .collect();
+            commands.insert_resource(GameKeySounds(key_sounds));
             info!("completed loading chart");
+            if !bundle.warnings.is_empty() {
+                ev.write(ChartLoadingEvent::Warnings(bundle.warnings));
+            }
             ev.write(ChartLoadingEvent::Success(bundle.path));
         }
     }
@@ -22,6 +22,8 @@ use self::{
     inspector::Inspector,
 };
 
+pub use self::editing::{journal::Journal, ChartEditHistory};
+
 pub struct ExtensionsPlugin;
 
 impl Plugin for ExtensionsPlugin {
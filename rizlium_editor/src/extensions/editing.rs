@@ -7,15 +7,20 @@ use self::{note::note_editor_vertical, tool_config_window::tool_config};
 use bevy::prelude::*;
 use egui::{emath::RectTransform, vec2, Color32, Sense, Stroke, Ui, UiBuilder};
 use rizlium_chart::{chart::Spline, editing::EditHistory};
-use rizlium_render::{GameChart, GameTime};
+use rizlium_render::{ChartChangeEvent, GameChart, GameTime};
 use rust_i18n::t;
 use spline::SplineView;
 
+mod history;
+pub mod journal;
 pub mod note;
 mod spline;
+mod statistics;
+mod svg_import;
 pub mod timeline;
 mod tool_config_window;
 mod tool_select_bar;
+mod transform;
 mod undo_redo;
 pub mod world_view;
 
@@ -40,16 +45,94 @@ impl Plugin for Editing {
             t!("edit.tool_config.tab"),
             tool_config,
             resource_exists::<GameChart>,
+        )
+        .register_tab(
+            "edit.history",
+            t!("edit.history.tab"),
+            history::history_tab,
+            resource_exists::<GameChart>,
+        )
+        .register_tab(
+            "edit.transform",
+            t!("edit.transform.tab"),
+            transform::transform_tab,
+            resource_exists::<GameChart>,
+        )
+        .register_tab(
+            "edit.statistics",
+            t!("edit.statistics.tab"),
+            statistics::statistics_tab,
+            resource_exists::<GameChart>,
+        )
+        .register_tab(
+            "edit.svg_import",
+            t!("edit.svg_import.tab"),
+            svg_import::svg_import_tab,
+            resource_exists::<GameChart>,
         );
 
         app.add_plugins(world_view::WorldViewPlugin)
-            .init_resource::<ChartEditHistory>();
+            .init_resource::<ChartEditHistory>()
+            .init_resource::<svg_import::SvgImportForm>()
+            .init_resource::<svg_import::PendingSvg>()
+            .add_systems(
+                PostUpdate,
+                (
+                    journal::start_journal,
+                    journal::write_journal,
+                    forward_chart_changes,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                PostUpdate,
+                svg_import::poll_pending_svg
+                    .run_if(|pending: Res<svg_import::PendingSvg>| pending.is_pending()),
+            );
 
         app.reflect_system("edit.undo", t!("edit.undo.desc"), undo_redo::undo);
         app.reflect_system("edit.redo", t!("edit.redo.desc"), undo_redo::redo);
+        app.reflect_system(
+            "edit.history.next_branch",
+            t!("edit.history.next_branch.desc"),
+            history::next_branch,
+        )
+        .reflect_system(
+            "edit.history.previous_branch",
+            t!("edit.history.previous_branch.desc"),
+            history::previous_branch,
+        );
+        app.reflect_system(
+            "edit.journal.replay",
+            t!("edit.journal.replay.desc"),
+            journal::replay_journal,
+        )
+        .reflect_system(
+            "edit.journal.discard",
+            t!("edit.journal.discard.desc"),
+            journal::discard_journal,
+        );
+        app.reflect_system(
+            "edit.transform.mirror",
+            t!("edit.transform.mirror.desc"),
+            transform::mirror_chart,
+        )
+        .reflect_system(
+            "edit.svg_import.open",
+            t!("edit.svg_import.open.desc"),
+            svg_import::open_svg_action,
+        );
         use KeyCode::*;
         app.register_hotkey("edit.undo", [Hotkey::new_global([ControlLeft, KeyZ])])
-            .register_hotkey("edit.redo", [Hotkey::new_global([ControlLeft, KeyY])]);
+            .register_hotkey("edit.redo", [Hotkey::new_global([ControlLeft, KeyY])])
+            .register_hotkey(
+                "edit.history.next_branch",
+                [Hotkey::new_global([ControlLeft, AltLeft, KeyY])],
+            )
+            .register_hotkey(
+                "edit.history.previous_branch",
+                [Hotkey::new_global([ControlLeft, AltLeft, KeyZ])],
+            );
         app.menu_context(|mut ctx| {
             ctx.with_sub_menu("edit", t!("edit.name"), 3, |mut ctx| {
                 ctx.add(
@@ -72,6 +155,30 @@ impl Plugin for Editing {
                     ),
                     1,
                 );
+                ctx.add(
+                    "replay_journal",
+                    t!("edit.journal.replay.name"),
+                    Button::new_conditioned(
+                        "edit.journal.replay",
+                        resource_exists::<journal::PendingRecovery>,
+                    ),
+                    2,
+                );
+                ctx.add(
+                    "discard_journal",
+                    t!("edit.journal.discard.name"),
+                    Button::new_conditioned(
+                        "edit.journal.discard",
+                        resource_exists::<journal::PendingRecovery>,
+                    ),
+                    3,
+                );
+                ctx.add(
+                    "mirror",
+                    t!("edit.transform.mirror.name"),
+                    Button::new_conditioned("edit.transform.mirror", resource_exists::<GameChart>),
+                    4,
+                );
             });
         });
     }
@@ -80,6 +187,17 @@ impl Plugin for Editing {
 #[derive(Deref, DerefMut, Resource, Default)]
 pub struct ChartEditHistory(EditHistory);
 
+/// 将编辑历史记录的改动转发给渲染部分.
+fn forward_chart_changes(
+    mut history: ResMut<ChartEditHistory>,
+    mut events: EventWriter<ChartChangeEvent>,
+) {
+    let changes = history.take_changes();
+    if !changes.is_empty() {
+        events.write_batch(changes.into_iter().map(ChartChangeEvent));
+    }
+}
+
 fn note_window(
     InMut(ui): InMut<Ui>,
     chart: Res<GameChart>,
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use egui::{RichText, Ui};
+use helium_framework::prelude::*;
+use rizlium_chart::editing::EditHistory;
+use rizlium_render::GameChart;
+
+use super::ChartEditHistory;
+
+pub fn history_tab(
+    InMut(ui): InMut<Ui>,
+    mut history: ResMut<ChartEditHistory>,
+    chart: Option<ResMut<GameChart>>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let mut jump_to = None;
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        show_node(ui, &history, history.root(), &mut jump_to);
+    });
+    let (Some(target), Some(mut chart)) = (jump_to, chart) else {
+        return;
+    };
+    if let Err(e) = history.jump_to(target, &mut chart) {
+        toasts.error(e.to_string());
+    }
+}
+
+/// 单链依次向下排列, 出现分支时每个分支缩进显示.
+fn show_node(ui: &mut Ui, history: &EditHistory, mut index: usize, jump_to: &mut Option<usize>) {
+    loop {
+        let node = &history.nodes()[index];
+        let mut text = RichText::new(node.description());
+        if !is_applied(history, index) {
+            text = text.weak();
+        }
+        if ui
+            .selectable_label(index == history.current(), text)
+            .clicked()
+        {
+            *jump_to = Some(index);
+        }
+        match node.children() {
+            [] => break,
+            [only] => index = *only,
+            children => {
+                for child in children {
+                    ui.indent(child, |ui| show_node(ui, history, *child, jump_to));
+                }
+                break;
+            }
+        }
+    }
+}
+
+fn is_applied(history: &EditHistory, index: usize) -> bool {
+    std::iter::successors(Some(history.current()), |i| history.nodes()[*i].parent())
+        .any(|i| i == index)
+}
+
+pub fn next_branch(
+    mut history: ResMut<ChartEditHistory>,
+    mut chart: ResMut<GameChart>,
+    mut notice: ResMut<ToastsStorage>,
+) {
+    if let Err(e) = history.switch_branch(1, &mut chart) {
+        notice.error(e.to_string());
+    }
+}
+
+pub fn previous_branch(
+    mut history: ResMut<ChartEditHistory>,
+    mut chart: ResMut<GameChart>,
+    mut notice: ResMut<ToastsStorage>,
+) {
+    if let Err(e) = history.switch_branch(-1, &mut chart) {
+        notice.error(e.to_string());
+    }
+}
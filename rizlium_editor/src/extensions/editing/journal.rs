@@ -0,0 +1,269 @@
+//! 编辑日志: 把每条应用到谱面上的命令追加写入谱面旁的文件, 以便在崩溃后恢复.
+//!
+//! 日志文件第一行是 [`JournalHeader`], 说明日志基于哪个谱面, 之后每行是一条 [`ChartCommands`].
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use helium_framework::prelude::ToastsStorage;
+use rizlium_chart::{editing::ChartCommands, prelude::Chart};
+use rizlium_render::GameChart;
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+
+use crate::{chart_loader::load_bundle_chart, ChartLoadingEvent};
+
+use super::ChartEditHistory;
+
+/// 日志所基于的谱面.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalBase {
+    /// 从谱面包中加载的谱面.
+    Bundle,
+    /// 最后一次保存的谱面 (谱面包旁的 `.rzl` 文件).
+    Saved,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct JournalHeader {
+    base: JournalBase,
+}
+
+#[derive(Resource, Debug)]
+pub struct Journal {
+    chart_path: String,
+    base: JournalBase,
+    header_written: bool,
+    saving: bool,
+    recovering: bool,
+    buffer: Vec<ChartCommands>,
+}
+
+/// 加载谱面时发现的未保存编辑.
+#[derive(Resource)]
+pub struct PendingRecovery {
+    base: JournalBase,
+    commands: Vec<ChartCommands>,
+}
+
+pub(super) fn journal_path(chart_path: &str) -> PathBuf {
+    PathBuf::from(format!("{chart_path}.journal"))
+}
+
+/// 与 [`crate::save_chart`] 保存的位置一致.
+pub(super) fn saved_chart_path(chart_path: &str) -> PathBuf {
+    PathBuf::from(format!("{chart_path}.rzl"))
+}
+
+impl Journal {
+    fn new(chart_path: String) -> Self {
+        Self {
+            chart_path,
+            base: JournalBase::Bundle,
+            header_written: false,
+            saving: false,
+            recovering: false,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        journal_path(&self.chart_path)
+    }
+
+    /// 记录新应用的命令. 保存进行中或等待恢复时先留在内存中.
+    pub fn record(&mut self, commands: Vec<ChartCommands>) -> io::Result<()> {
+        self.buffer.extend(commands);
+        if self.saving || self.recovering || self.buffer.is_empty() {
+            return Ok(());
+        }
+        let mut file = if self.header_written {
+            OpenOptions::new().append(true).open(self.path())?
+        } else {
+            let mut file = File::create(self.path())?;
+            serde_json::to_writer(&mut file, &JournalHeader { base: self.base })?;
+            writeln!(file)?;
+            self.header_written = true;
+            file
+        };
+        for command in self.buffer.drain(..) {
+            serde_json::to_writer(&mut file, &command)?;
+            writeln!(file)?;
+        }
+        Ok(())
+    }
+
+    /// 开始保存. 调用前应先 [`Self::record`] 所有已应用到被保存谱面的命令.
+    pub fn begin_save(&mut self) {
+        self.saving = true;
+    }
+
+    /// 保存结束. 成功时删除旧的日志, 之后的日志改为基于已保存的谱面.
+    ///
+    /// 等待恢复时旧的日志仍需保留.
+    pub fn finish_save(&mut self, succeeded: bool) -> io::Result<()> {
+        self.saving = false;
+        if succeeded {
+            self.base = JournalBase::Saved;
+            self.header_written = false;
+        }
+        if succeeded && !self.recovering {
+            match fs::remove_file(self.path()) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => (),
+            }
+        }
+        self.record(Vec::new())
+    }
+}
+
+fn read_journal(path: &Path) -> io::Result<PendingRecovery> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+    let header: JournalHeader = match lines.next() {
+        Some(line) => serde_json::from_str(&line?)?,
+        None => return Err(io::ErrorKind::UnexpectedEof.into()),
+    };
+    let mut commands = Vec::new();
+    for (index, line) in lines.enumerate() {
+        match line.and_then(|line| Ok(serde_json::from_str(&line)?)) {
+            Ok(command) => commands.push(command),
+            Err(err) => {
+                // 崩溃时最后一行可能只写了一半, 保留之前的命令.
+                warn!(
+                    "dropping torn journal tail from line {} of {}: {err}",
+                    index + 2,
+                    path.display()
+                );
+                break;
+            }
+        }
+    }
+    Ok(PendingRecovery {
+        base: header.base,
+        commands,
+    })
+}
+
+pub(super) fn start_journal(
+    mut commands: Commands,
+    mut events: EventReader<ChartLoadingEvent>,
+    mut history: ResMut<ChartEditHistory>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let Some(path) = events
+        .read()
+        .filter_map(|event| match event {
+            ChartLoadingEvent::Success(path) => Some(path),
+            ChartLoadingEvent::Warnings(_) | ChartLoadingEvent::Error(_) => None,
+        })
+        .last()
+    else {
+        return;
+    };
+    *history = ChartEditHistory::default();
+    history.set_journaling(true);
+    let mut journal = Journal::new(path.clone());
+    commands.remove_resource::<PendingRecovery>();
+    let file = journal_path(path);
+    if file.exists() {
+        match read_journal(&file) {
+            Ok(recovery) if !recovery.commands.is_empty() => {
+                journal.recovering = true;
+                toasts.info(t!("edit.journal.found", count = recovery.commands.len()));
+                commands.insert_resource(recovery);
+            }
+            Ok(_) => (),
+            Err(err) => warn!("ignoring unreadable journal {}: {err}", file.display()),
+        }
+    }
+    commands.insert_resource(journal);
+}
+
+pub(super) fn write_journal(
+    journal: Option<ResMut<Journal>>,
+    mut history: ResMut<ChartEditHistory>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let commands = history.take_journal();
+    let Some(mut journal) = journal else {
+        return;
+    };
+    if commands.is_empty() {
+        return;
+    }
+    if let Err(err) = journal.record(commands) {
+        toasts.error(t!("edit.journal.write_failed", err = err));
+    }
+}
+
+pub(super) fn replay_journal(
+    mut commands: Commands,
+    recovery: Option<Res<PendingRecovery>>,
+    journal: Option<ResMut<Journal>>,
+    chart: Option<ResMut<GameChart>>,
+    mut history: ResMut<ChartEditHistory>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let (Some(recovery), Some(mut journal), Some(mut chart)) = (recovery, journal, chart) else {
+        return;
+    };
+    // 等待恢复时谱面可能已被编辑, 从谱面包重新读取日志所基于的谱面.
+    let base = match recovery.base {
+        JournalBase::Bundle => load_bundle_chart(&journal.chart_path).map_err(Into::into),
+        JournalBase::Saved => read_saved_chart(&saved_chart_path(&journal.chart_path)),
+    };
+    let mut replayed = match base {
+        Ok(base) => base,
+        Err(err) => {
+            toasts.error(t!("edit.journal.replay_failed", err = err));
+            return;
+        }
+    };
+    // 逐条加入历史, 使恢复的编辑可以撤销. 它们已经在日志中, 重放时不再记录.
+    let mut recovered = ChartEditHistory::default();
+    for command in recovery.commands.iter().cloned() {
+        recovered.break_coalescing();
+        if let Err(err) = recovered.push(command, &mut replayed) {
+            toasts.error(t!("edit.journal.replay_failed", err = err));
+            return;
+        }
+    }
+    // 谱面会被整个替换, 不需要逐条转发改动.
+    recovered.take_changes();
+    recovered.set_journaling(true);
+    *chart = GameChart::new(replayed);
+    *history = recovered;
+    // 日志文件已经描述了恢复后的谱面, 之后的编辑直接追加.
+    journal.base = recovery.base;
+    journal.header_written = true;
+    journal.recovering = false;
+    journal.buffer.clear();
+    commands.remove_resource::<PendingRecovery>();
+    toasts.success(t!("edit.journal.replayed"));
+}
+
+pub(super) fn discard_journal(
+    mut commands: Commands,
+    journal: Option<ResMut<Journal>>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let Some(mut journal) = journal else {
+        return;
+    };
+    if let Err(err) = fs::remove_file(journal.path()) {
+        warn!("failed to remove journal: {err}");
+    }
+    journal.header_written = false;
+    journal.recovering = false;
+    commands.remove_resource::<PendingRecovery>();
+    if let Err(err) = journal.record(Vec::new()) {
+        toasts.error(t!("edit.journal.write_failed", err = err));
+    }
+}
+
+fn read_saved_chart(path: &Path) -> Result<Chart, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
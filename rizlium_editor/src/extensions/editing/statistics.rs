@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use egui::{vec2, DragValue, Rect, Sense, Ui};
+use helium_framework::prelude::*;
+use rizlium_chart::analysis::statistics::{
+    statistics, ChartStatistics, DifficultyWeights, StatisticsConfig,
+};
+use rizlium_render::{GameChart, GameChartCache};
+use rust_i18n::t;
+
+#[derive(Default)]
+pub(super) struct StatisticsState {
+    weights: DifficultyWeights,
+    stats: Option<ChartStatistics>,
+}
+
+pub(super) fn statistics_tab(
+    InMut(ui): InMut<Ui>,
+    mut state: Local<StatisticsState>,
+    chart: Res<GameChart>,
+    cache: Option<Res<GameChartCache>>,
+) {
+    let Some(cache) = cache else {
+        return;
+    };
+    // 谱面或缓存改变时才重新统计.
+    if state.stats.is_none() || chart.is_changed() || cache.is_changed() {
+        state.stats = Some(statistics(&chart, &cache, &StatisticsConfig::default()));
+    }
+    let StatisticsState { weights, stats } = &mut *state;
+    let Some(stats) = stats else {
+        return;
+    };
+    let counts = &stats.counts;
+    egui::Grid::new("statistics").num_columns(2).show(ui, |ui| {
+        ui.label(t!("edit.statistics.notes"));
+        ui.label(t!(
+            "edit.statistics.notes.value",
+            total = counts.total(),
+            tap = counts.tap,
+            drag = counts.drag,
+            hold = counts.hold
+        ));
+        ui.end_row();
+        ui.label(t!("edit.statistics.duration"));
+        ui.label(format!("{:.2}s", stats.duration));
+        ui.end_row();
+        ui.label(t!("edit.statistics.notes_per_second"));
+        ui.label(format!("{:.2}", stats.notes_per_second()));
+        ui.end_row();
+        ui.label(t!("edit.statistics.peak"));
+        ui.label(stats.peak.map_or_else(String::new, |peak| {
+            format!(
+                "{:.2} ({:.2}s - {:.2}s)",
+                peak.per_second(),
+                peak.start,
+                peak.end
+            )
+        }));
+        ui.end_row();
+        ui.label(t!("edit.statistics.hold_coverage"));
+        ui.label(format!("{:.1}%", stats.hold_coverage * 100.));
+        ui.end_row();
+        ui.label(t!("edit.statistics.speed"));
+        ui.label(format!(
+            "{:.0} / {:.0}",
+            stats.average_speed, stats.max_speed
+        ));
+        ui.end_row();
+        ui.label(t!("edit.statistics.bpm"));
+        ui.label(
+            stats
+                .bpm_range
+                .map_or_else(String::new, |(min, max)| format!("{min} - {max}")),
+        );
+        ui.end_row();
+        ui.label(t!("edit.statistics.difficulty"));
+        ui.strong(format!("{:.2}", weights.estimate(stats)));
+        ui.end_row();
+    });
+
+    ui.separator();
+    density_bars(ui, &stats.density);
+
+    ui.collapsing(t!("edit.statistics.weights"), |ui| {
+        egui::Grid::new("difficulty_weights")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for (label, value) in [
+                    (t!("edit.statistics.notes_per_second"), &mut weights.density),
+                    (t!("edit.statistics.peak"), &mut weights.peak_density),
+                    (
+                        t!("edit.statistics.average_speed"),
+                        &mut weights.average_speed,
+                    ),
+                    (t!("edit.statistics.max_speed"), &mut weights.max_speed),
+                    (
+                        t!("edit.statistics.hold_coverage"),
+                        &mut weights.hold_coverage,
+                    ),
+                    (t!("edit.statistics.drag_ratio"), &mut weights.drag_ratio),
+                ] {
+                    ui.label(label);
+                    ui.add(DragValue::new(value).speed(0.01));
+                    ui.end_row();
+                }
+            });
+        if ui.button(t!("edit.statistics.reset_weights")).clicked() {
+            *weights = default();
+        }
+    });
+}
+
+/// 每秒音符数的柱状图.
+fn density_bars(ui: &mut Ui, density: &[f32]) {
+    let (response, painter) = ui.allocate_painter(vec2(ui.available_width(), 80.), Sense::hover());
+    let rect = response.rect;
+    let max = density.iter().copied().fold(0., f32::max);
+    if density.is_empty() || max <= 0. {
+        return;
+    }
+    let width = rect.width() / density.len() as f32;
+    let color = ui.visuals().selection.bg_fill;
+    for (i, value) in density.iter().enumerate() {
+        let height = rect.height() * value / max;
+        let left = rect.left() + width * i as f32;
+        painter.rect_filled(
+            Rect::from_min_max(
+                egui::pos2(left, rect.bottom() - height),
+                egui::pos2(left + width, rect.bottom()),
+            ),
+            0.,
+            color,
+        );
+    }
+    response.on_hover_text(t!("edit.statistics.density", max = format!("{max:.2}")));
+}
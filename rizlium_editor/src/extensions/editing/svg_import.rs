@@ -0,0 +1,167 @@
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task},
+};
+use egui::{DragValue, Ui};
+use futures_lite::future;
+use helium_framework::prelude::*;
+use rfd::AsyncFileDialog;
+use rizlium_chart::{
+    editing::commands::{ChartCommands, CommandSequence, InsertLine},
+    parse::svg::{self, SvgImportConfig},
+};
+use rizlium_render::{GameChart, GameChartCache, GameTime};
+use rust_i18n::t;
+
+use super::ChartEditHistory;
+
+#[derive(Resource, Default)]
+pub(super) struct SvgImportForm {
+    /// SVG 文件内容或路径数据.
+    text: String,
+    config: SvgImportConfig,
+}
+
+#[derive(Resource, Default)]
+pub(super) struct PendingSvg(Option<Task<Option<String>>>);
+
+impl PendingSvg {
+    pub(super) fn is_pending(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+fn import_lines(
+    form: &SvgImportForm,
+    history: &mut ChartEditHistory,
+    chart: &mut GameChart,
+    cache: &GameChartCache,
+    toasts: &mut ToastsStorage,
+) {
+    let lines = match svg::import(&form.text, cache, &form.config) {
+        Ok(lines) => lines,
+        Err(e) => {
+            toasts.error(e.to_string());
+            return;
+        }
+    };
+    let count = lines.len();
+    let command = CommandSequence {
+        commands: lines
+            .into_iter()
+            .map(|line| ChartCommands::from(InsertLine { line, at: None }))
+            .collect(),
+    };
+    match history.push(command, chart) {
+        Ok(()) => toasts.success(t!("edit.svg_import.done", count = count)),
+        Err(e) => toasts.error(e.to_string()),
+    }
+}
+
+pub(super) fn svg_import_tab(
+    InMut(ui): InMut<Ui>,
+    mut form: ResMut<SvgImportForm>,
+    mut pending: ResMut<PendingSvg>,
+    mut history: ResMut<ChartEditHistory>,
+    mut chart: ResMut<GameChart>,
+    cache: Option<Res<GameChartCache>>,
+    time: Res<GameTime>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let Some(cache) = cache else {
+        return;
+    };
+    let canvas_count = chart.canvases.len().max(1);
+    let config = &mut form.config;
+    egui::Grid::new("svg_import").num_columns(2).show(ui, |ui| {
+        ui.label(t!("edit.svg_import.canvas"));
+        ui.add(DragValue::new(&mut config.canvas).range(0..=canvas_count - 1));
+        ui.end_row();
+        ui.label(t!("edit.svg_import.start_time"));
+        ui.horizontal(|ui| {
+            ui.add(DragValue::new(&mut config.start_time).speed(0.125));
+            if ui.button(t!("edit.svg_import.current_time")).clicked() {
+                config.start_time = **time;
+            }
+        });
+        ui.end_row();
+        ui.label(t!("edit.svg_import.scale"));
+        ui.add(DragValue::new(&mut config.scale).speed(0.01));
+        ui.end_row();
+        ui.label(t!("edit.svg_import.x_offset"));
+        ui.add(DragValue::new(&mut config.x_offset));
+        ui.end_row();
+        ui.label(t!("edit.svg_import.tolerance"));
+        ui.add(
+            DragValue::new(&mut config.tolerance)
+                .speed(0.1)
+                .range(0.01..=100.0),
+        );
+        ui.end_row();
+    });
+    ui.label(t!("edit.svg_import.path"));
+    ui.add(
+        egui::TextEdit::multiline(&mut form.text)
+            .code_editor()
+            .desired_rows(4)
+            .desired_width(f32::INFINITY),
+    );
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(
+                !form.text.trim().is_empty(),
+                egui::Button::new(t!("edit.svg_import.apply")),
+            )
+            .clicked()
+        {
+            import_lines(&form, &mut history, &mut chart, &cache, &mut toasts);
+        }
+        if ui
+            .add_enabled(
+                !pending.is_pending(),
+                egui::Button::new(t!("edit.svg_import.open")),
+            )
+            .clicked()
+        {
+            open_svg(&mut pending);
+        }
+    });
+}
+
+/// 选择 SVG 文件, 读取后以导入面板中的设置导入.
+fn open_svg(pending: &mut PendingSvg) {
+    pending.0 = Some(IoTaskPool::get().spawn(async {
+        let file = AsyncFileDialog::new()
+            .add_filter(t!("edit.svg_import.file_type"), &["svg"])
+            .pick_file()
+            .await?;
+        async_fs::read_to_string(file.path()).await.ok()
+    }));
+}
+
+pub(super) fn open_svg_action(mut pending: ResMut<PendingSvg>) {
+    open_svg(&mut pending);
+}
+
+pub(super) fn poll_pending_svg(
+    mut pending: ResMut<PendingSvg>,
+    mut form: ResMut<SvgImportForm>,
+    mut history: ResMut<ChartEditHistory>,
+    chart: Option<ResMut<GameChart>>,
+    cache: Option<Res<GameChartCache>>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let Some(text) = pending
+        .0
+        .as_mut()
+        .and_then(|task| future::block_on(future::poll_once(task)))
+    else {
+        return;
+    };
+    pending.0 = None;
+    let (Some(text), Some(mut chart), Some(cache)) = (text, chart, cache) else {
+        return;
+    };
+    form.text = text;
+    import_lines(&form, &mut history, &mut chart, &cache, &mut toasts);
+}
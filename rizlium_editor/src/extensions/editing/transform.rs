@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+use egui::{DragValue, Ui};
+use helium_framework::prelude::*;
+use rizlium_chart::{
+    chart::{EasingId, KeyPoint, Spline},
+    editing::{
+        chart_path::LinePath,
+        commands::{ChartTransform, Quantize, QuantizeItem, TransformChart, TransformScope},
+    },
+};
+use rizlium_render::{GameChart, GameChartCache};
+use rust_i18n::t;
+use strum::EnumIter;
+
+use crate::{
+    extensions::inspector::{ChartItem, SelectedItem},
+    widgets::enum_selector,
+};
+
+use super::ChartEditHistory;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter)]
+enum TransformKind {
+    #[default]
+    Mirror,
+    ShiftTime,
+    StretchTime,
+    ScaleX,
+    RemapBpm,
+}
+
+/// 量化的范围.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter)]
+enum QuantizeScope {
+    /// 选中的音符或线点, 选中线时为整条线.
+    #[default]
+    Selected,
+    SelectedLine,
+    Chart,
+}
+
+struct TransformForm {
+    kind: TransformKind,
+    selected_line_only: bool,
+    /// 轴, 偏移量或中心.
+    origin: f32,
+    factor: f32,
+    bpm: f32,
+    /// 量化到 1/division 拍.
+    division: u32,
+    quantize_scope: QuantizeScope,
+}
+
+impl Default for TransformForm {
+    fn default() -> Self {
+        Self {
+            kind: default(),
+            selected_line_only: false,
+            origin: 0.,
+            factor: 1.,
+            bpm: 120.,
+            division: 4,
+            quantize_scope: default(),
+        }
+    }
+}
+
+impl TransformForm {
+    fn transform(&self) -> ChartTransform {
+        match self.kind {
+            TransformKind::Mirror => ChartTransform::Mirror { axis: self.origin },
+            TransformKind::ShiftTime => ChartTransform::ShiftTime {
+                offset: self.origin,
+            },
+            TransformKind::StretchTime => ChartTransform::StretchTime {
+                pivot: self.origin,
+                factor: self.factor,
+            },
+            TransformKind::ScaleX => ChartTransform::ScaleX {
+                origin: self.origin,
+                factor: self.factor,
+            },
+            TransformKind::RemapBpm => ChartTransform::RemapBpm {
+                bpm: Spline::from(vec![KeyPoint {
+                    time: 0.,
+                    value: self.bpm,
+                    ease_type: EasingId::Start,
+                    relevant: (),
+                }]),
+            },
+        }
+    }
+}
+
+pub(super) fn transform_tab(
+    InMut(ui): InMut<Ui>,
+    mut form: Local<TransformForm>,
+    selected: Res<SelectedItem>,
+    mut history: ResMut<ChartEditHistory>,
+    mut chart: ResMut<GameChart>,
+    cache: Option<Res<GameChartCache>>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let form = &mut *form;
+    ui.horizontal_wrapped(|ui| {
+        ui.label(t!("edit.transform.kind"));
+        enum_selector(&mut form.kind, ui);
+    });
+    let selected_line = selected.line();
+    ui.add_enabled(
+        selected_line.is_some() && form.kind != TransformKind::RemapBpm,
+        egui::Checkbox::new(
+            &mut form.selected_line_only,
+            t!("edit.transform.selected_line_only"),
+        ),
+    );
+    ui.horizontal_wrapped(|ui| match form.kind {
+        TransformKind::Mirror => {
+            ui.label(t!("edit.transform.axis"));
+            ui.add(DragValue::new(&mut form.origin));
+        }
+        TransformKind::ShiftTime => {
+            ui.label(t!("edit.transform.offset"));
+            ui.add(DragValue::new(&mut form.origin).speed(0.125));
+        }
+        TransformKind::StretchTime | TransformKind::ScaleX => {
+            ui.label(t!("edit.transform.origin"));
+            ui.add(DragValue::new(&mut form.origin));
+            ui.label(t!("edit.transform.factor"));
+            ui.add(DragValue::new(&mut form.factor).speed(0.01));
+        }
+        TransformKind::RemapBpm => {
+            ui.label(t!("edit.transform.bpm"));
+            ui.add(DragValue::new(&mut form.bpm).range(1.0..=1000.0));
+        }
+    });
+    if ui.button(t!("edit.transform.apply")).clicked() {
+        let scope = match selected_line {
+            Some(line) if form.selected_line_only && form.kind != TransformKind::RemapBpm => {
+                TransformScope::Lines(vec![line])
+            }
+            _ => TransformScope::Chart,
+        };
+        let command = TransformChart {
+            scope,
+            transform: form.transform(),
+        };
+        if let Err(e) = history.push(command, &mut chart) {
+            toasts.error(e.to_string());
+        }
+    }
+
+    ui.separator();
+    let quantize = ui
+        .horizontal_wrapped(|ui| {
+            ui.label(t!("edit.quantize.scope"));
+            enum_selector(&mut form.quantize_scope, ui);
+            ui.label(t!("edit.quantize.division"));
+            ui.add(DragValue::new(&mut form.division).range(1..=64));
+            let enabled = match form.quantize_scope {
+                QuantizeScope::Selected | QuantizeScope::SelectedLine => selected_line.is_some(),
+                QuantizeScope::Chart => true,
+            };
+            ui.add_enabled(enabled, egui::Button::new(t!("edit.quantize.apply")))
+                .clicked()
+        })
+        .inner;
+    if !quantize {
+        return;
+    }
+    let command = match (form.quantize_scope, selected.item.as_ref()) {
+        (QuantizeScope::Selected, Some(ChartItem::Note(note))) => Quantize {
+            items: vec![QuantizeItem::Note(*note)],
+            division: form.division,
+        },
+        (QuantizeScope::Selected, Some(ChartItem::LinePoint(point))) => Quantize {
+            items: vec![QuantizeItem::Point(*point)],
+            division: form.division,
+        },
+        (QuantizeScope::Chart, _) => {
+            Quantize::lines(&chart, (0..chart.lines.len()).map(LinePath), form.division)
+        }
+        _ => {
+            let Some(line) = selected_line else {
+                return;
+            };
+            Quantize::lines(&chart, [line], form.division)
+        }
+    };
+    let moves = match command.moves(&chart) {
+        Ok(moves) => moves,
+        Err(e) => {
+            toasts.error(e.to_string());
+            return;
+        }
+    };
+    if let Err(e) = history.push(command, &mut chart) {
+        toasts.error(e.to_string());
+        return;
+    }
+    let moved = moves.iter().filter(|m| m.from != m.to).count();
+    let max_millis = cache.map_or(0., |cache| {
+        moves
+            .iter()
+            .map(|m| m.millis(&cache).abs())
+            .fold(0., f32::max)
+    });
+    toasts.success(t!(
+        "edit.quantize.done",
+        count = moved,
+        millis = format!("{max_millis:.1}")
+    ));
+}
+
+/// 以 x = 0 为轴翻转整个谱面.
+pub(super) fn mirror_chart(
+    mut history: ResMut<ChartEditHistory>,
+    mut chart: ResMut<GameChart>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let command = TransformChart {
+        scope: TransformScope::Chart,
+        transform: ChartTransform::Mirror { axis: 0. },
+    };
+    if let Err(e) = history.push(command, &mut chart) {
+        toasts.error(e.to_string());
+    }
+}
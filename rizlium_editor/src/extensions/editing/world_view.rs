@@ -34,6 +34,7 @@ use self::{
 use super::tool_select_bar;
 
 pub mod cam_response;
+mod line_actions;
 pub(super) mod tools;
 pub struct WorldViewPlugin;
 
@@ -50,6 +51,21 @@ impl Plugin for WorldViewPlugin {
             world_tab,
             || true,
         );
+        app.reflect_system(
+            "edit.world_view.split_line",
+            t!("edit.world_view.split_line.desc"),
+            line_actions::split_line,
+        )
+        .reflect_system(
+            "edit.world_view.join_next_line",
+            t!("edit.world_view.join_next_line.desc"),
+            line_actions::join_next_line,
+        )
+        .reflect_system(
+            "edit.world_view.duplicate_line",
+            t!("edit.world_view.duplicate_line.desc"),
+            line_actions::duplicate_line,
+        );
     }
 }
 
@@ -0,0 +1,88 @@
+//! 对选中的线进行的操作.
+use bevy::prelude::*;
+use helium_framework::prelude::*;
+use rizlium_chart::editing::{
+    chart_path::LinePath,
+    commands::{ChartCommands, DuplicateLine, JoinLines, SplitLine},
+};
+use rizlium_render::{GameChart, GameTime};
+
+use crate::extensions::{
+    editing::ChartEditHistory,
+    inspector::{ChartItem, SelectedItem},
+};
+
+fn push(
+    command: impl Into<ChartCommands>,
+    history: &mut ChartEditHistory,
+    chart: &mut GameChart,
+    toasts: &mut ToastsStorage,
+) -> bool {
+    match history.push(command, chart) {
+        Ok(()) => true,
+        Err(e) => {
+            toasts.error(e.to_string());
+            false
+        }
+    }
+}
+
+/// 在当前时间切开选中的线.
+pub(super) fn split_line(
+    mut selected: ResMut<SelectedItem>,
+    time: Res<GameTime>,
+    mut history: ResMut<ChartEditHistory>,
+    mut chart: ResMut<GameChart>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let Some(line_path) = selected.line() else {
+        return;
+    };
+    let command = SplitLine {
+        line_path,
+        time: **time,
+    };
+    if push(command, &mut history, &mut chart, &mut toasts) {
+        selected.item = Some(ChartItem::Line(line_path));
+    }
+}
+
+/// 把下一条线接到选中的线之后.
+pub(super) fn join_next_line(
+    mut selected: ResMut<SelectedItem>,
+    mut history: ResMut<ChartEditHistory>,
+    mut chart: ResMut<GameChart>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let Some(first) = selected.line() else {
+        return;
+    };
+    let command = JoinLines {
+        first,
+        second: LinePath(first.0 + 1),
+    };
+    if push(command, &mut history, &mut chart, &mut toasts) {
+        selected.item = Some(ChartItem::Line(first));
+    }
+}
+
+/// 原位复制选中的线, 并选中副本.
+pub(super) fn duplicate_line(
+    mut selected: ResMut<SelectedItem>,
+    mut history: ResMut<ChartEditHistory>,
+    mut chart: ResMut<GameChart>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
+    let Some(line_path) = selected.line() else {
+        return;
+    };
+    let command = DuplicateLine {
+        line_path,
+        time_offset: 0.,
+        x_offset: 0.,
+        at: None,
+    };
+    if push(command, &mut history, &mut chart, &mut toasts) {
+        selected.item = Some(ChartItem::Line(LinePath(line_path.0 + 1)));
+    }
+}
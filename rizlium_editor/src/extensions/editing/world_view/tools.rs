@@ -222,6 +222,12 @@ fn pencil_tool(
         history.submit_preedit();
     }
     for event in mouse_events.read() {
+        // 一次拖动中的编辑合并为一条历史.
+        match event.event.event_type {
+            MouseEventType::Drag(DragEventType::DragStarted) => history.begin_gesture(),
+            MouseEventType::Drag(DragEventType::DragEnded) => history.end_gesture(),
+            _ => (),
+        }
         if let Some(data) = current_edit.as_ref() {
             let event = &event.event;
             if matches!(event.event_type, MouseEventType::Click(_)) {
@@ -234,13 +240,19 @@ fn pencil_tool(
                             point_idx: None,
                             point: KeyPoint {
                                 time: to_game
-                                    .time_at_y(event.pos.y, pencil_config.canvas)
+                                    .line_time_at_y(
+                                        &chart,
+                                        data.line_idx,
+                                        event.pos.y,
+                                        pencil_config.canvas,
+                                    )
                                     .unwrap(),
                                 value: event.pos.x,
                                 ease_type: pencil_config.easing,
                                 relevant: LinePointData {
                                     canvas: pencil_config.canvas,
                                     color: color32_to_colorrgba(pencil_config.pen_color),
+                                    ..Default::default()
                                 },
                             },
                         },
@@ -260,13 +272,19 @@ fn pencil_tool(
                             point_idx: data.point_idx,
                             new_time: Some(
                                 to_game
-                                    .time_at_y(event.pos.y, pencil_config.canvas)
+                                    .line_time_at_y(
+                                        &chart,
+                                        data.line_idx,
+                                        event.pos.y,
+                                        pencil_config.canvas,
+                                    )
                                     .unwrap(),
                             ),
                             new_x: Some(event.pos.x),
                             new_canvas: Some(pencil_config.canvas),
                             new_color: Some(color32_to_colorrgba(pencil_config.pen_color)),
                             new_easing: Some(pencil_config.easing),
+                            new_style: None,
                         },
                         &mut chart,
                     )
@@ -328,6 +346,7 @@ fn get_point(
         relevant: LinePointData {
             color: color32_to_colorrgba(pencil_config.pen_color),
             canvas: pencil_config.canvas,
+            ..Default::default()
         },
     }
 }
@@ -1,10 +1,11 @@
 use bevy::{prelude::*, render::view::VisibleEntities};
-use egui::{ScrollArea, Ui};
+use egui::{DragValue, ScrollArea, Ui};
 use rizlium_chart::{
-    chart::Chart,
+    chart::{Chart, ColorRGBA},
     editing::{
         chart_path::{ChartPath, LinePath, LinePointPath},
-        NotePath,
+        commands::{EditPoint, SetNoteProperties},
+        ChartCommands, NotePath,
     },
 };
 use rizlium_render::{GameCamera, GameChart};
@@ -12,7 +13,7 @@ use rust_i18n::t;
 
 use helium_framework::prelude::*;
 
-use crate::RizliumDockStateMirror;
+use crate::{widgets::enum_selector, RizliumDockStateMirror};
 
 use super::editing::{world_view::cam_response::WorldMouseEvent, ChartEditHistory};
 
@@ -21,6 +22,18 @@ pub struct SelectedItem {
     pub item: Option<ChartItem>,
 }
 
+impl SelectedItem {
+    /// 选中的对象所在的线.
+    pub fn line(&self) -> Option<LinePath> {
+        match self.item.as_ref()? {
+            ChartItem::Line(line) => Some(*line),
+            ChartItem::LinePoint(LinePointPath(line, _)) | ChartItem::Note(NotePath(line, _)) => {
+                Some(*line)
+            }
+        }
+    }
+}
+
 pub enum ChartItem {
     LinePoint(LinePointPath),
     Line(LinePath),
@@ -47,14 +60,21 @@ impl Plugin for Inspector {
     }
 }
 
-fn logs(InMut(mut ui): InMut<Ui>, chart: Res<GameChart>, selected: Res<SelectedItem>) {
+fn logs(
+    InMut(mut ui): InMut<Ui>,
+    mut chart: ResMut<GameChart>,
+    selected: Res<SelectedItem>,
+    mut history: ResMut<ChartEditHistory>,
+    mut toasts: ResMut<ToastsStorage>,
+) {
     let Some(ref item) = selected.item else {
         ui.weak(t!("tab.logs.select_to_inspect"));
         return;
     };
     let ui = &mut ui;
-    if let ChartItem::LinePoint(l) = item {
-        show_ui(ui, *l, &chart, |ui, line_point| {
+    let mut drag = DragGesture::default();
+    let command: Option<ChartCommands> = match item {
+        ChartItem::LinePoint(l) => show_ui(ui, *l, &chart, |ui, line_point| {
             ui.columns(2, |columns| {
                 columns[0].label("easing:");
                 columns[1].label(format!("{:?}", line_point.ease_type));
@@ -62,23 +82,134 @@ fn logs(InMut(mut ui): InMut<Ui>, chart: Res<GameChart>, selected: Res<SelectedI
                 columns[1].label(line_point.time.to_string());
                 columns[0].label("canvas:");
                 columns[1].label(line_point.relevant.canvas.to_string());
-            });
-        })
+                let mut style = line_point.relevant.style;
+                let mut changed = false;
+                columns[0].label("width:");
+                changed |= drag
+                    .track(columns[1].add(DragValue::new(&mut style.width).range(0.0..=f32::MAX)));
+                columns[0].label("dash:");
+                changed |= columns[1]
+                    .horizontal(|ui| {
+                        drag.track(ui.add(DragValue::new(&mut style.dash).range(0.0..=f32::MAX)))
+                            | drag
+                                .track(ui.add(DragValue::new(&mut style.gap).range(0.0..=f32::MAX)))
+                    })
+                    .inner;
+                columns[0].label("glow:");
+                changed |= drag
+                    .track(columns[1].add(DragValue::new(&mut style.glow).range(0.0..=f32::MAX)));
+                changed.then(|| {
+                    EditPoint {
+                        line_path: l.0,
+                        point_idx: l.1,
+                        new_time: None,
+                        new_x: None,
+                        new_canvas: None,
+                        new_color: None,
+                        new_easing: None,
+                        new_style: Some(style),
+                    }
+                    .into()
+                })
+            })
+        }),
+        ChartItem::Note(n) => show_ui(ui, *n, &chart, |ui, note| {
+            let mut properties = note.properties.clone();
+            ui.columns(2, |columns| {
+                columns[0].label("time:");
+                columns[1].label(note.time.to_string());
+                columns[0].label("kind:");
+                columns[1].label(format!("{:?}", note.kind));
+                let mut changed = false;
+                columns[0].label("color:");
+                changed |= columns[1]
+                    .horizontal(|ui| {
+                        let mut overridden = properties.color.is_some();
+                        let toggled = ui.checkbox(&mut overridden, "").changed();
+                        let color = properties.color.unwrap_or(ColorRGBA::WHITE);
+                        let mut rgba = [color.r, color.g, color.b, color.a];
+                        let edited = ui
+                            .add_enabled_ui(overridden, |ui| {
+                                ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed()
+                            })
+                            .inner;
+                        let [r, g, b, a] = rgba;
+                        properties.color = overridden.then_some(ColorRGBA::new(r, g, b, a));
+                        toggled | edited
+                    })
+                    .inner;
+                columns[0].label("scale:");
+                changed |= drag.track(
+                    columns[1].add(
+                        DragValue::new(&mut properties.scale)
+                            .speed(0.01)
+                            .range(0.0..=f32::MAX),
+                    ),
+                );
+                columns[0].label("variant:");
+                let variant = properties.variant;
+                enum_selector(&mut properties.variant, &mut columns[1]);
+                changed |= variant != properties.variant;
+                columns[0].label("sound:");
+                let mut sound = properties.sound.clone().unwrap_or_default();
+                if columns[1].text_edit_singleline(&mut sound).changed() {
+                    properties.sound = (!sound.is_empty()).then_some(sound);
+                    changed = true;
+                }
+                changed.then(|| {
+                    SetNoteProperties {
+                        note_path: *n,
+                        properties,
+                    }
+                    .into()
+                })
+            })
+        }),
+        ChartItem::Line(_) => None,
+    };
+    if drag.started {
+        history.begin_gesture();
+    }
+    if let Some(command) = command {
+        if let Err(e) = history.push(command, &mut chart) {
+            toasts.error(e.to_string());
+        }
+    }
+    if drag.stopped {
+        history.end_gesture();
     }
 }
 
+/// 记录本帧拖动的开始与结束, 使一次拖动中的编辑合并为一条历史.
+#[derive(Default)]
+struct DragGesture {
+    started: bool,
+    stopped: bool,
+}
+
+impl DragGesture {
+    /// 返回控件的值是否改变.
+    fn track(&mut self, response: egui::Response) -> bool {
+        self.started |= response.drag_started();
+        self.stopped |= response.drag_stopped();
+        response.changed()
+    }
+}
+
+/// 显示 `item_path` 处的对象, 返回 `show` 产生的编辑命令.
 fn show_ui<P: ChartPath>(
     ui: &mut Ui,
     item_path: P,
     chart: &Chart,
-    show: impl FnOnce(&mut Ui, &P::Out),
-) {
+    show: impl FnOnce(&mut Ui, &P::Out) -> Option<ChartCommands>,
+) -> Option<ChartCommands> {
     match item_path.get(chart) {
         Ok(item) => show(ui, item),
         Err(err) => {
             ui.colored_label(egui::Color32::RED, err.to_string());
+            None
         }
-    };
+    }
 }
 
 fn bevy_inspector(InMut(ui): InMut<Ui>, world: &mut World) {
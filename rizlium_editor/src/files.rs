@@ -8,7 +8,10 @@ use rfd::AsyncFileDialog;
 use rust_i18n::t;
 use serde::{Deserialize, Serialize};
 
-use crate::{ChartLoadingEvent, EditorCommands};
+use crate::{
+    extensions::{ChartEditHistory, Journal},
+    ChartLoadingEvent, EditorCommands,
+};
 use helium_framework::prelude::ToastsStorage;
 use rizlium_render::GameChart;
 
@@ -67,6 +70,14 @@ fn report_error_or_add_current(
             ChartLoadingEvent::Error(err) => {
                 toasts.error(t!("chart.load.fail", err = err));
             }
+            ChartLoadingEvent::Warnings(warnings) => {
+                let warnings = warnings
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                toasts.info(t!("chart.load.warnings", warnings = warnings));
+            }
             ChartLoadingEvent::Success(path) => {
                 toasts.success(t!("chart.load.success"));
                 commands.insert_resource(CurrentChartPath(path.clone()));
@@ -83,6 +94,8 @@ pub fn save_chart(
     current_path: Option<Res<CurrentChartPath>>,
     mut toasts: ResMut<ToastsStorage>,
     mut save: ResMut<PendingSave>,
+    mut history: ResMut<ChartEditHistory>,
+    journal: Option<ResMut<Journal>>,
 ) {
     let (Some(chart), Some(current_path)) = (chart, current_path) else {
         toasts.error("No chart loaded");
@@ -101,6 +114,13 @@ pub fn save_chart(
         .unwrap_or(std::borrow::Cow::Borrowed("chart"));
     let target = parent.join(name.into_owned() + ".rzl");
     let owned_chart = (**chart).clone();
+    if let Some(mut journal) = journal {
+        // 已经包含在被保存谱面中的命令不应再出现在新的日志里
+        if let Err(err) = journal.record(history.take_journal()) {
+            toasts.error(format!("error encountered while writing journal: {err}"));
+        }
+        journal.begin_save();
+    }
     let task: Task<Result<(), Box<dyn std::error::Error + Send + Sync>>> =
         IoTaskPool::get().spawn(async move {
             let mut file = async_fs::File::create(target).await?;
@@ -115,7 +135,11 @@ pub fn save_chart(
 #[derive(Resource, Default)]
 pub struct PendingSave(Option<Task<Result<(), Box<dyn std::error::Error + Send + Sync>>>>);
 
-fn poll_pending_save(mut save: ResMut<PendingSave>, mut toasts: ResMut<ToastsStorage>) {
+fn poll_pending_save(
+    mut save: ResMut<PendingSave>,
+    mut toasts: ResMut<ToastsStorage>,
+    journal: Option<ResMut<Journal>>,
+) {
     let Some(result) = save
         .0
         .as_mut()
@@ -124,6 +148,11 @@ fn poll_pending_save(mut save: ResMut<PendingSave>, mut toasts: ResMut<ToastsSto
         return;
     };
     save.0 = None;
+    if let Some(mut journal) = journal {
+        if let Err(err) = journal.finish_save(result.is_ok()) {
+            toasts.error(format!("error encountered while writing journal: {err}"));
+        }
+    }
     match result {
         Ok(()) => toasts.success("Chart saved!"),
         Err(err) => toasts.error(format!("error encountered while saving chart: {err}")),
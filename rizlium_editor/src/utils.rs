@@ -2,6 +2,7 @@ use bevy::ecs::{
     schedule::{BoxedCondition, Condition},
     system::{IntoSystem, Res, System, SystemParam},
 };
+use rizlium_chart::prelude::Chart;
 use rizlium_render::{GameChartCache, GameTime};
 
 #[derive(SystemParam)]
@@ -21,6 +22,18 @@ impl WorldToGame<'_> {
                     .canvas_y_at(canvas, **self.time.as_deref()?)?,
         )
     }
+    /// 与 [`Self::time_at_y`] 相同, 但计入第 `line_idx` 条线的速度倍率.
+    pub fn line_time_at_y(
+        &self,
+        chart: &Chart,
+        line_idx: usize,
+        world_y: f32,
+        canvas: usize,
+    ) -> Option<f32> {
+        chart
+            .with_cache(self.cache.as_deref()?)
+            .line_distance_to_time(line_idx, canvas, **self.time.as_deref()?, world_y)
+    }
     pub fn avalible(&self) -> bool {
         self.cache.is_some() && self.time.is_some()
     }
@@ -1,5 +1,8 @@
 use bevy::prelude::*;
-use rizlium_chart::prelude::*;
+use rizlium_chart::{
+    editing::{ChangeKind, ChangeTarget, ChartChange},
+    prelude::*,
+};
 #[derive(Resource, Deref, DerefMut)]
 pub struct GameChart(Chart); // TODO gate edit history behind this, so that invalid edit won't appear
 
@@ -24,26 +27,61 @@ impl GameChart {
 #[derive(Resource, Default, Deref)]
 pub struct GameChartCache(pub ChartCache);
 
+/// 对 [`GameChart`] 的一处已知改动, 通常由编辑历史发出.
+///
+/// 没有伴随任何事件的 [`GameChart`] 变化 (如加载新谱面) 视为范围未知.
+#[derive(Event, Debug, Clone, Copy, Deref)]
+pub struct ChartChangeEvent(pub ChartChange);
+
 pub struct ChartCachePlugin;
 
 impl Plugin for ChartCachePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            PreUpdate,
-            chart_cache.run_if(resource_exists_and_changed::<GameChart>),
-        );
+        app.add_event::<ChartChangeEvent>()
+            .add_systems(PreUpdate, chart_cache);
     }
 }
 fn chart_cache(
     mut commands: Commands,
-    chart: Res<GameChart>,
+    chart: Option<Res<GameChart>>,
     cache: Option<ResMut<GameChartCache>>,
+    mut changes: EventReader<ChartChangeEvent>,
 ) {
+    // 无论是否更新都要读完事件, 以免之后把旧的改动当作新的.
+    let changes: Vec<_> = changes.read().map(|change| change.0).collect();
+    let Some(chart) = chart.filter(|chart| chart.is_changed()) else {
+        return;
+    };
     let Some(mut cache) = cache else {
         info!("add cache");
         commands.insert_resource(GameChartCache(ChartCache::from_chart(&chart)));
         return;
     };
-    info!("update cache");
-    cache.0.update_from_chart(&chart);
+    if changes.is_empty()
+        || changes
+            .iter()
+            .any(|change| change.target == ChangeTarget::Bpm)
+    {
+        info!("update cache");
+        cache.0.update_from_chart(&chart);
+        return;
+    }
+    let mut canvases = Vec::new();
+    for change in changes.iter().filter(|change| change.affects_cache()) {
+        let ChangeTarget::Canvas(index) = change.target else {
+            continue;
+        };
+        if change.kind != ChangeKind::Modified {
+            debug!("update all canvases in cache");
+            cache.0.update_canvases(&chart);
+            return;
+        }
+        if !canvases.contains(&index) {
+            canvases.push(index);
+        }
+    }
+    for index in canvases {
+        debug!("update canvas {index} in cache");
+        cache.0.update_canvas(&chart, index);
+    }
 }
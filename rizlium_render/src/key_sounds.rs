@@ -0,0 +1,48 @@
+//! 音符经过判定线时播放谱面包中的击打音效.
+use bevy::{platform::collections::HashMap, prelude::*};
+use bevy_kira_audio::{Audio, AudioControl, AudioSource};
+
+use crate::{GameChart, GameTime};
+
+pub struct KeySoundPlugin;
+
+impl Plugin for KeySoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            play_key_sounds.run_if(chart_update!().and(resource_exists::<GameKeySounds>)),
+        );
+    }
+}
+
+/// 以谱面包中的路径索引的击打音效.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct GameKeySounds(pub HashMap<String, Handle<AudioSource>>);
+
+/// 两帧之间前进超过此值 (beat) 时视为跳转, 不播放跳过的音效.
+const MAX_STEP: f32 = 0.5;
+
+fn play_key_sounds(
+    time: Res<GameTime>,
+    chart: Res<GameChart>,
+    sounds: Res<GameKeySounds>,
+    audio: Res<Audio>,
+    mut last_time: Local<Option<f32>>,
+) {
+    let now = **time;
+    let Some(last) = last_time.replace(now) else {
+        return;
+    };
+    if now <= last || now - last > MAX_STEP {
+        return;
+    }
+    chart
+        .lines
+        .iter()
+        .flat_map(|line| &line.notes)
+        .filter(|note| last < note.time && note.time <= now)
+        .filter_map(|note| sounds.get(note.properties.sound.as_deref()?))
+        .for_each(|sound| {
+            audio.play(sound.clone());
+        });
+}
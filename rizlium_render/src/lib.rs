@@ -29,6 +29,8 @@ mod chart;
 mod line_rendering;
 pub use line_rendering::{ChartLine, ChartLineId, ShowLines};
 mod hit_parcticles;
+mod key_sounds;
+pub use key_sounds::GameKeySounds;
 mod theme;
 mod time_and_audio;
 
@@ -37,7 +39,7 @@ pub mod notes;
 pub use chart::*;
 pub use time_and_audio::*;
 
-use crate::hit_parcticles::HitParticlePlugin;
+use crate::{hit_parcticles::HitParticlePlugin, key_sounds::KeySoundPlugin};
 #[derive(Resource)]
 pub struct GameView(pub Handle<Image>);
 
@@ -80,6 +82,7 @@ impl Plugin for RizliumRenderingPlugin {
                 RingPlugin,
                 MaskPlugin,
                 HitParticlePlugin,
+                KeySoundPlugin,
             ))
             .add_systems(Startup, spawn_game_camera)
             .add_systems(PostUpdate, bind_gameview);
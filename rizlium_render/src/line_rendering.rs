@@ -1,12 +1,12 @@
 use bevy::ecs::component::Tick;
 use bevy_prototype_lyon::prelude::tess::geom::euclid::approxeq::ApproxEq;
-use rizlium_chart::chart::{EasingId, KeyPoint, LinePointData, Tween};
+use rizlium_chart::chart::{EasingId, KeyPoint, LinePointData, LineStyle, Tween};
 
 use bevy_prototype_lyon::prelude::*;
 
 use bevy::{prelude::*, render::view::RenderLayers};
 
-use bevy::render::primitives::Aabb;
+use bevy::{math::Vec3A, render::primitives::Aabb};
 
 use crate::GameChartCache;
 
@@ -57,6 +57,8 @@ pub struct ChartLineBundle {
     line: ChartLine,
     shape: ShapeBundle,
     stoke: Stroke,
+    /// 线宽沿线段变化时, 线段画为填充的轮廓.
+    fill: Fill,
     synced_tick: LastSyncTick,
 }
 impl Default for ChartLineBundle {
@@ -67,15 +69,50 @@ impl Default for ChartLineBundle {
             shape: default(),
             stoke: Stroke {
                 options: StrokeOptions::default()
-                    .with_line_width(10.)
+                    .with_line_width(LineStyle::DEFAULT.width)
                     .with_line_cap(LineCap::Round),
                 brush: Brush::Color(Color::NONE),
             },
+            fill: Fill {
+                options: FillOptions::default().with_fill_rule(FillRule::NonZero),
+                brush: Brush::Color(Color::NONE),
+            },
             synced_tick: LastSyncTick::ZERO,
         }
     }
 }
 
+/// 线段的发光, 作为线段的子实体以更宽的半透明描边画在线段之后.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
+pub struct ChartLineGlow;
+
+#[derive(Bundle)]
+struct ChartLineGlowBundle {
+    glow: ChartLineGlow,
+    shape: ShapeBundle,
+    stroke: Stroke,
+    /// 只使用 `shape`, 记录上次同步发光的时刻.
+    synced_tick: LastSyncTick,
+}
+
+impl Default for ChartLineGlowBundle {
+    fn default() -> Self {
+        Self {
+            glow: default(),
+            shape: default(),
+            stroke: Stroke {
+                options: StrokeOptions::default().with_line_cap(LineCap::Round),
+                brush: Brush::Color(Color::NONE),
+            },
+            synced_tick: LastSyncTick::ZERO,
+        }
+    }
+}
+
+/// 发光相对线本身的不透明度.
+const GLOW_ALPHA: f32 = 0.35;
+
 pub struct ChartLinePlugin;
 
 use super::chart_update;
@@ -95,7 +132,11 @@ impl Plugin for ChartLinePlugin {
             )
             .add_systems(
                 PostUpdate,
-                (change_bounding, update_shape, update_stroke, update_layer)
+                (
+                    (change_bounding, update_shape, update_stroke, update_layer),
+                    update_glow,
+                )
+                    .chain()
                     .in_set(LineRenderingSystemSet::Rendering)
                     .run_if(chart_update!()),
             );
@@ -108,7 +149,9 @@ fn add_segments(mut commands: Commands, chart: Res<GameChart>, lines: Query<&Cha
     let delta = segment_count - now_count;
     debug!("attempting to add {delta} segments");
     for _ in now_count..segment_count {
-        commands.spawn(ChartLineBundle::default());
+        commands
+            .spawn(ChartLineBundle::default())
+            .with_child(ChartLineGlowBundle::default());
     }
 }
 
@@ -224,8 +267,7 @@ fn update_shape(
                 return;
             };
 
-            let mut builder = PathBuilder::new();
-            builder.move_to(Vec2::ZERO);
+            let mut points = vec![Vec2::ZERO];
             let relative_pos = [pos2[0] - pos1[0], pos2[1] - pos1[1]];
             if !(keypoint1.ease_type == EasingId::Linear
                 || pos1[0].approx_eq(&pos2[0])
@@ -235,7 +277,7 @@ fn update_shape(
                 if point_count >= 10000. {
                     point_count = 5000.
                 }
-                builder.reserve(point_count as usize);
+                points.reserve(point_count as usize);
                 // 0...>1...>2...>3..0'
                 (1..point_count as usize)
                     .map(|i| i as f32 / point_count)
@@ -245,24 +287,134 @@ fn update_shape(
                             <f32 as rizlium_chart::chart::Tween>::lerp(0., relative_pos[1], t),
                         ]
                     })
-                    .for_each(|p| {
-                        builder.line_to(p.into());
-                    });
+                    .for_each(|p| points.push(p.into()));
             }
-            builder.line_to(relative_pos.into());
+            points.push(relative_pos.into());
             // connect next segment
             if let Some(pos) =
                 chart
                     .with_cache(&cache)
                     .line_pos_at(line_idx, keypoint2.time + 0.01, **time)
             {
-                builder.line_to(Vec2::from_array(pos) - Vec2::from_array(pos1));
+                points.push(Vec2::from_array(pos) - Vec2::from_array(pos1));
+            }
+            let style = |t| line.style_at(keypoint_idx, t).unwrap_or_default();
+            let points = with_progress(&points);
+            // 虚线的长度取自线段起点, 不随线段插值.
+            let runs = if style(0.).is_dashed() {
+                dash_runs(&points, style(0.))
+            } else {
+                vec![points]
+            };
+            let mut builder = PathBuilder::new();
+            if width_varies(line, keypoint_idx) {
+                runs.iter()
+                    .for_each(|run| outline_run(&mut builder, run, |t| style(t).width));
+            } else {
+                runs.iter().filter(|run| !run.is_empty()).for_each(|run| {
+                    builder.move_to(run[0].0);
+                    run[1..].iter().for_each(|(p, _)| {
+                        builder.line_to(*p);
+                    });
+                });
             }
             *path = builder.build();
             synced.shape = chart.last_changed();
         });
 }
 
+/// 最短的实线或间隔长度, 避免长度为 0 时无法前进.
+const MIN_DASH: f32 = 0.5;
+
+/// 半圆线头所用的点数.
+const CAP_SEGMENTS: usize = 8;
+
+/// 线宽是否沿线段变化. lyon 的描边在一条路径上宽度不变, 此时需要画填充的轮廓.
+fn width_varies(line: &rizlium_chart::prelude::Line, keypoint_idx: usize) -> bool {
+    let width = |t| line.style_at(keypoint_idx, t).map(|style| style.width);
+    width(0.) != width(1.)
+}
+
+/// 给折线的每个点加上沿折线的长度比例.
+fn with_progress(points: &[Vec2]) -> Vec<(Vec2, f32)> {
+    let total: f32 = points.windows(2).map(|w| w[0].distance(w[1])).sum();
+    let mut travelled = 0.;
+    let mut last = points.first().copied().unwrap_or_default();
+    points
+        .iter()
+        .map(|&point| {
+            travelled += last.distance(point);
+            last = point;
+            let progress = if total > 0. { travelled / total } else { 0. };
+            (point, progress)
+        })
+        .collect()
+}
+
+/// 按 `style` 的虚线样式把折线切为若干段实线.
+fn dash_runs(points: &[(Vec2, f32)], style: LineStyle) -> Vec<Vec<(Vec2, f32)>> {
+    let mut runs = Vec::new();
+    let mut run: Vec<_> = points.first().copied().into_iter().collect();
+    let mut drawing = true;
+    // 当前的实线或间隔剩余的长度.
+    let mut remaining = style.dash.max(MIN_DASH);
+    for w in points.windows(2) {
+        let (mut from, to) = (w[0], w[1]);
+        let mut length = from.0.distance(to.0);
+        while length > remaining {
+            let ratio = remaining / length;
+            let point = (from.0.lerp(to.0, ratio), from.1 + (to.1 - from.1) * ratio);
+            if drawing {
+                run.push(point);
+                runs.push(std::mem::take(&mut run));
+            } else {
+                run = vec![point];
+            }
+            length -= remaining;
+            from = point;
+            drawing = !drawing;
+            remaining = if drawing { style.dash } else { style.gap }.max(MIN_DASH);
+        }
+        if drawing {
+            run.push(to);
+        }
+        remaining -= length;
+    }
+    if drawing && run.len() > 1 {
+        runs.push(run);
+    }
+    runs
+}
+
+/// 把一段折线画为填充的轮廓, 宽度按长度比例由 `width` 给出, 两端为半圆.
+fn outline_run(builder: &mut PathBuilder, run: &[(Vec2, f32)], width: impl Fn(f32) -> f32) {
+    if run.len() < 2 {
+        return;
+    }
+    let last = run.len() - 1;
+    let direction =
+        |i: usize| (run[(i + 1).min(last)].0 - run[i.saturating_sub(1)].0).normalize_or_zero();
+    let offset = |i: usize| direction(i).perp() * width(run[i].1) / 2.;
+    let cap = |builder: &mut PathBuilder, i: usize, forward: Vec2| {
+        let radius = width(run[i].1) / 2.;
+        let side = forward.perp();
+        (1..CAP_SEGMENTS).for_each(|step| {
+            let angle = step as f32 / CAP_SEGMENTS as f32 * std::f32::consts::PI;
+            builder.line_to(run[i].0 + (side * angle.cos() + forward * angle.sin()) * radius);
+        });
+    };
+    builder.move_to(run[0].0 + offset(0));
+    (1..=last).for_each(|i| {
+        builder.line_to(run[i].0 + offset(i));
+    });
+    cap(builder, last, direction(last));
+    (0..=last).rev().for_each(|i| {
+        builder.line_to(run[i].0 - offset(i));
+    });
+    cap(builder, 0, -direction(0));
+    builder.close();
+}
+
 const DEBUG_INVISIBLE: Color = Color::LinearRgba(LinearRgba::new(1., 0., 1., 0.2));
 
 fn update_stroke(
@@ -271,7 +423,7 @@ fn update_stroke(
     time: Res<GameTime>,
     mut lines: Query<(
         &mut Stroke,
-        &mut Path,
+        &mut Fill,
         &ViewVisibility,
         &ChartLineId,
         &mut LastSyncTick,
@@ -279,7 +431,7 @@ fn update_stroke(
 ) {
     lines
         .iter_mut()
-        .for_each(|(mut stroke, _, vis, id, mut synced)| {
+        .for_each(|(mut stroke, mut fill, vis, id, mut synced)| {
             if !vis.get() {
                 return;
             }
@@ -323,11 +475,123 @@ fn update_stroke(
                 end: relative_pos,
                 stops: vec![GradientStop::new(0., color1), GradientStop::new(1., color2)],
             };
-            stroke.brush = Brush::Gradient(gradient.into());
+            let brush = Brush::Gradient(gradient.into());
+            if width_varies(line, keypoint_idx) {
+                fill.brush = brush;
+                stroke.brush = Brush::Color(Color::NONE);
+            } else {
+                stroke.brush = brush;
+                fill.brush = Brush::Color(Color::NONE);
+            }
+            // 线宽变化时描边不可见, 只用来确定包围盒.
+            let [start, end] = [0., 1.].map(|t| line.style_at(keypoint_idx, t).unwrap_or_default());
+            stroke.options.line_width = start.width.max(end.width);
             synced.color = chart.last_changed();
         });
 }
 
+fn update_glow(
+    chart: Res<GameChart>,
+    cache: Res<GameChartCache>,
+    time: Res<GameTime>,
+    lines: Query<
+        (
+            Ref<Path>,
+            Ref<Aabb>,
+            &RenderLayers,
+            &ChartLineId,
+            &ViewVisibility,
+        ),
+        Without<ChartLineGlow>,
+    >,
+    mut glows: Query<
+        (
+            &ChildOf,
+            &mut Path,
+            &mut Stroke,
+            &mut Aabb,
+            &mut RenderLayers,
+            &mut Transform,
+            &mut Visibility,
+            &mut LastSyncTick,
+        ),
+        With<ChartLineGlow>,
+    >,
+) {
+    for (child_of, mut path, mut stroke, mut aabb, mut layer, mut transform, mut vis, mut synced) in
+        &mut glows
+    {
+        let Ok((line_path, line_aabb, line_layer, id, view_vis)) = lines.get(child_of.parent())
+        else {
+            continue;
+        };
+        if !view_vis.get() {
+            continue;
+        }
+        // 线段, 谱面与时间都没有变化时发光不变.
+        let last_changed = [
+            line_path.last_changed(),
+            line_aabb.last_changed(),
+            chart.last_changed(),
+            time.last_changed(),
+        ]
+        .into_iter()
+        .max_by_key(|tick| tick.get())
+        .unwrap();
+        if synced.shape.get() >= last_changed.get() {
+            continue;
+        }
+        synced.shape = last_changed;
+        let Some(line) = chart.lines.get(id.line_idx) else {
+            continue;
+        };
+        let style = line.style_at(id.keypoint_idx, 0.5).unwrap_or_default();
+        if style.glow <= 0. {
+            vis.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+        vis.set_if_neq(Visibility::Inherited);
+        let (Some(pos1), Some(pos2)) = (
+            chart
+                .with_cache(&cache)
+                .pos_for_linepoint_at(id.line_idx, id.keypoint_idx, **time),
+            chart
+                .with_cache(&cache)
+                .pos_for_linepoint_at(id.line_idx, id.keypoint_idx + 1, **time),
+        ) else {
+            continue;
+        };
+        let glow_color = |keypoint_idx| {
+            let color = get_color_of(line, keypoint_idx, **time);
+            color.with_alpha(color.alpha() * GLOW_ALPHA)
+        };
+        stroke.brush = Brush::Gradient(
+            LinearGradient {
+                start: Vec2::ZERO,
+                end: Vec2::from(pos2) - Vec2::from(pos1),
+                stops: vec![
+                    GradientStop::new(0., glow_color(id.keypoint_idx)),
+                    GradientStop::new(1., glow_color(id.keypoint_idx + 1)),
+                ],
+            }
+            .into(),
+        );
+        // 线宽变化时线段的路径已经是轮廓, 只需向外扩展.
+        stroke.options.line_width = if width_varies(line, id.keypoint_idx) {
+            style.glow * 2.
+        } else {
+            style.width + style.glow * 2.
+        };
+        *path = (*line_path).clone();
+        *aabb = Aabb {
+            center: line_aabb.center,
+            half_extents: line_aabb.half_extents + Vec3A::new(style.glow, style.glow, 0.),
+        };
+        layer.set_if_neq(line_layer.clone());
+        transform.translation.z = -0.1;
+    }
+}
+
 fn get_color_of(line: &rizlium_chart::prelude::Line, keypoint_idx: usize, time: f32) -> Color {
     colorrgba_to_color({
         let point_color = line
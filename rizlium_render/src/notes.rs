@@ -1,11 +1,16 @@
 use bevy::{platform::collections::HashMap, prelude::*, render::primitives::Aabb};
 use bevy_prototype_lyon::{prelude::*, shapes::Circle};
-use rizlium_chart::chart::NoteKind;
+use rizlium_chart::chart::{NoteKind, NoteVariant};
 
 use crate::{colorrgba_to_color, hit_parcticles::HasHit, GameChart, GameChartCache, GameTime};
 
 pub const NOTE_Z: f32 = 5.;
 
+/// [`NoteVariant::Highlight`] 的边框颜色.
+const HIGHLIGHT_COLOR: Color = Color::srgb(1., 0.84, 0.3);
+/// [`NoteVariant::Faint`] 的不透明度.
+const FAINT_ALPHA: f32 = 0.4;
+
 pub struct ChartNotePlugin;
 
 impl Plugin for ChartNotePlugin {
@@ -88,31 +93,57 @@ fn update_note(
     cache: Res<GameChartCache>,
     game_time: Res<GameTime>,
     mut notes: Query<(&mut Transform, &ChartNoteId, &Children)>,
-    mut note_bg: Query<&mut Sprite, With<note_tags::NoteBg>>,
+    mut sprites: Query<(
+        &mut Sprite,
+        Has<note_tags::NoteBg>,
+        Has<note_tags::NoteFrame>,
+    )>,
 ) {
     notes
         .iter_mut()
         .for_each(|(mut transform, note_id, child)| {
-            let time;
-            {
-                let Some(line) = chart.lines.get(note_id.line_idx) else {
-                    return;
-                };
-                let Some(note) = line.notes.get(note_id.note_idx) else {
-                    return;
-                };
-                time = note.time;
-            }
+            let Some(note) = chart
+                .lines
+                .get(note_id.line_idx)
+                .and_then(|line| line.notes.get(note_id.note_idx))
+            else {
+                return;
+            };
+            let time = note.time;
+            let properties = &note.properties;
             let chart_with_cache = chart.with_cache(&cache);
             let pos: Vec2 = chart_with_cache
                 .line_pos_at_clamped(note_id.line_idx, time, **game_time)
                 .unwrap()
                 .into();
-            *transform = transform.with_translation(pos.extend(NOTE_Z));
+            *transform = transform
+                .with_translation(pos.extend(NOTE_Z))
+                .with_scale(Vec3::splat(properties.scale));
+            let alpha = match properties.variant {
+                NoteVariant::Faint => FAINT_ALPHA,
+                _ => 1.,
+            };
+            let highlight = if properties.variant == NoteVariant::Highlight {
+                HIGHLIGHT_COLOR
+            } else {
+                Color::WHITE
+            };
+            let override_color = properties.color.map(colorrgba_to_color);
             for child in child.iter() {
-                if let Ok(mut sprite) = note_bg.get_mut(child) {
-                    sprite.color = colorrgba_to_color(chart.theme_at(time).unwrap().this.color.note);
-                }
+                let Ok((mut sprite, is_bg, is_frame)) = sprites.get_mut(child) else {
+                    continue;
+                };
+                let color = if is_frame {
+                    highlight
+                } else if is_bg {
+                    override_color.unwrap_or_else(|| {
+                        colorrgba_to_color(chart.theme_at(time).unwrap().this.color.note)
+                    })
+                } else {
+                    // 长条和 drag 没有单独的底色, 整个贴图按指定的颜色染色.
+                    override_color.unwrap_or(highlight)
+                };
+                sprite.color = color.with_alpha(color.alpha() * alpha);
             }
         });
 }